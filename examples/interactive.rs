@@ -49,6 +49,7 @@ pub fn main() {
             style: bevy_iced::iced::renderer::Style {
                 text_color: bevy_iced::iced::Color::from_rgb(0.0, 1.0, 1.0),
             },
+            ..Default::default()
         })
         .add_startup_system(build_program)
         .add_system(tick)
@@ -0,0 +1,50 @@
+use bevy::prelude::*;
+use bevy::render::camera::RenderTarget;
+use bevy::window::WindowRef;
+use bevy_iced::iced::widget::text;
+use bevy_iced::{IcedContext, IcedPlugin};
+
+pub enum UiMessage {}
+
+#[derive(Resource)]
+pub struct SecondWindow(Entity);
+
+pub fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(IcedPlugin)
+        .add_event::<UiMessage>()
+        .add_startup_system(setup)
+        .add_system(ui_system)
+        .run();
+}
+
+fn setup(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+
+    let second_window = commands
+        .spawn(Window {
+            title: "Second window".to_owned(),
+            ..Default::default()
+        })
+        .id();
+    commands.spawn(Camera2dBundle {
+        camera: Camera {
+            target: RenderTarget::Window(WindowRef::Entity(second_window)),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    commands.insert_resource(SecondWindow(second_window));
+}
+
+fn ui_system(time: Res<Time>, second_window: Res<SecondWindow>, mut ctx: IcedContext<UiMessage>) {
+    ctx.display(text("This is the primary window."));
+    ctx.display_in(
+        second_window.0,
+        text(format!(
+            "This is the second window. Running for {:.2} seconds.",
+            time.elapsed_seconds()
+        )),
+    );
+}
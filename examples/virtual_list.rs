@@ -0,0 +1,58 @@
+use bevy::prelude::*;
+use bevy_iced::iced::widget::scrollable::RelativeOffset;
+use bevy_iced::iced::widget::{scrollable, text, vertical_space, Column};
+use bevy_iced::iced_lazy::responsive;
+use bevy_iced::{IcedContext, IcedPlugin};
+
+const ROWS: usize = 100_000;
+const ROW_HEIGHT: f32 = 24.0;
+
+#[derive(Clone)]
+enum UiMessage {
+    Scrolled(RelativeOffset),
+}
+
+#[derive(Resource, Default)]
+pub struct ListState {
+    offset: f32,
+}
+
+pub fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugin(IcedPlugin::default())
+        .add_event::<UiMessage>()
+        .init_resource::<ListState>()
+        .add_startup_system(build_program)
+        .add_system(scroll_system)
+        .add_system(ui_system)
+        .run();
+}
+
+fn build_program(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+fn scroll_system(mut messages: EventReader<UiMessage>, mut list: ResMut<ListState>) {
+    for UiMessage::Scrolled(offset) in messages.iter() {
+        list.offset = offset.y;
+    }
+}
+
+// Only the rows in view are built, between spaces as tall as the rows above and below them.
+fn ui_system(list: Res<ListState>, mut ctx: IcedContext<UiMessage>) {
+    let offset = list.offset;
+    ctx.display(responsive(move |size| {
+        let scrolled = (ROWS as f32 * ROW_HEIGHT - size.height).max(0.0) * offset;
+        let first = ((scrolled / ROW_HEIGHT) as usize).min(ROWS);
+        let last = (first + (size.height / ROW_HEIGHT) as usize + 2).min(ROWS);
+        let rows = (first..last)
+            .map(|row| text(format!("Row {row}")).height(ROW_HEIGHT).into())
+            .collect();
+        let content = Column::new()
+            .push(vertical_space(first as f32 * ROW_HEIGHT))
+            .push(Column::with_children(rows))
+            .push(vertical_space((ROWS - last) as f32 * ROW_HEIGHT));
+        scrollable(content).on_scroll(UiMessage::Scrolled).into()
+    }));
+}
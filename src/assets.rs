@@ -0,0 +1,286 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+
+use bevy_asset::{
+    AssetEvent, AssetLoader, Assets, BoxedFuture, Error, HandleId, LoadContext, LoadedAsset,
+};
+use bevy_ecs::prelude::EventReader;
+#[cfg(feature = "image")]
+use bevy_ecs::system::SystemParam;
+use bevy_ecs::system::{Res, ResMut, Resource};
+use bevy_reflect::TypeUuid;
+#[cfg(feature = "image")]
+use bevy_render::render_resource::TextureFormat;
+#[cfg(feature = "image")]
+use bevy_render::texture::Image;
+use bevy_utils::HashMap;
+#[cfg(feature = "image")]
+use iced_native::image;
+#[cfg(feature = "svg")]
+use iced_native::svg;
+use iced_native::Font;
+
+#[cfg(feature = "image")]
+use crate::render::TrimCaches;
+
+/// A font file loaded through the `AssetServer`, to use in the UI with [`IcedFonts`].
+///
+/// Fonts are loaded from files with the `.iced.ttf` or `.iced.otf` extension, which leaves
+/// plain `.ttf` and `.otf` files to Bevy's own text rendering.
+///
+/// The renderer needs the data of the fonts it uses for the lifetime of the app, so the data
+/// of each distinct font is leaked once it's registered in [`IcedFonts`]. Loading the same
+/// file again reuses it, but every edit of a hot-reloaded font keeps its own copy.
+#[derive(TypeUuid, Debug)]
+#[uuid = "37d3301d-168c-440b-86b7-23145304330e"]
+pub struct IcedFont {
+    bytes: Arc<[u8]>,
+}
+
+#[derive(Default)]
+pub(crate) struct IcedFontLoader;
+
+impl AssetLoader for IcedFontLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let bytes = Arc::from(bytes);
+            load_context.set_default_asset(LoadedAsset::new(IcedFont { bytes }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["iced.ttf", "iced.otf"]
+    }
+}
+
+/// An SVG file loaded through the `AssetServer`, to show in Iced `svg` widgets.
+///
+/// Reloaded files replace the asset, so fetching the handle from `Assets<IcedSvg>` every frame
+/// shows the latest version.
+/// ```ignore
+/// fn ui_system(svgs: Res<Assets<IcedSvg>>, icons: Res<Icons>, mut ctx: IcedContext<UiMessage>) {
+///     if let Some(logo) = svgs.get(&icons.logo) {
+///         ctx.display(svg(logo.handle()));
+///     }
+/// }
+/// ```
+#[cfg(feature = "svg")]
+#[derive(TypeUuid, Debug)]
+#[uuid = "c4e9688a-98eb-46e9-a622-cb2bfd1822ef"]
+pub struct IcedSvg {
+    handle: svg::Handle,
+}
+
+#[cfg(feature = "svg")]
+impl IcedSvg {
+    /// The handle to pass to an Iced `svg` widget.
+    pub fn handle(&self) -> svg::Handle {
+        self.handle.clone()
+    }
+}
+
+#[cfg(feature = "svg")]
+#[derive(Default)]
+pub(crate) struct IcedSvgLoader;
+
+#[cfg(feature = "svg")]
+impl AssetLoader for IcedSvgLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), Error>> {
+        Box::pin(async move {
+            let handle = svg::Handle::from_memory(bytes.to_vec());
+            load_context.set_default_asset(LoadedAsset::new(IcedSvg { handle }));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["svg"]
+    }
+}
+
+/// The fonts available to text widgets.
+///
+/// Contains the fonts passed to [`IcedPlugin::with_fonts`](crate::IcedPlugin::with_fonts)
+/// and every loaded [`IcedFont`] asset. Asset fonts are replaced when the file is reloaded.
+/// ```ignore
+/// fn ui_system(fonts: Res<IcedFonts>, menu: Res<MenuFont>, mut ctx: IcedContext<UiMessage>) {
+///     ctx.display(column![
+///         text("Game Over").font(fonts[0]),
+///         text("Continue?").font(fonts.asset(&menu.0)),
+///     ]);
+/// }
+/// ```
+#[derive(Resource, Clone, Debug, Default)]
+pub struct IcedFonts {
+    fonts: Vec<Font>,
+    assets: HashMap<HandleId, Font>,
+    registered: Vec<Font>,
+}
+
+impl IcedFonts {
+    pub(crate) fn new(fonts: &[&'static [u8]]) -> Self {
+        let mut this = Self::default();
+        this.fonts = fonts
+            .iter()
+            .map(|&bytes| this.register(Cow::Borrowed(bytes)))
+            .collect();
+        this
+    }
+
+    // Fonts are cached by name in the renderer, so every distinct font gets its own name, which
+    // is leaked along with its data the first time it's registered.
+    fn register(&mut self, bytes: Cow<'static, [u8]>) -> Font {
+        let bytes = intern_font(bytes);
+        let registered = self.registered.iter().find(|font| match font {
+            Font::External {
+                bytes: registered, ..
+            } => std::ptr::eq(*registered, bytes),
+            Font::Default => false,
+        });
+        if let Some(font) = registered {
+            return *font;
+        }
+        let name = format!("bevy_iced font {}", self.registered.len());
+        let font = Font::External {
+            name: Box::leak(name.into_boxed_str()),
+            bytes,
+        };
+        self.registered.push(font);
+        font
+    }
+
+    /// The font at the given position in the list passed to the plugin.
+    pub fn get(&self, index: usize) -> Option<Font> {
+        self.fonts.get(index).copied()
+    }
+
+    /// The font of a loaded [`IcedFont`] asset, or the default font while it is loading.
+    pub fn asset(&self, handle: impl Into<HandleId>) -> Font {
+        self.assets
+            .get(&handle.into())
+            .copied()
+            .unwrap_or(Font::Default)
+    }
+}
+
+impl std::ops::Index<usize> for IcedFonts {
+    type Output = Font;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.fonts[index]
+    }
+}
+
+// The data of every font given to a renderer, which keeps it for the lifetime of the app.
+static FONTS: Mutex<Vec<&'static [u8]>> = Mutex::new(Vec::new());
+
+// Returns the data of a font with the lifetime of the app, leaking it unless the same font was
+// interned before, so that loading a font again doesn't leak it again.
+pub(crate) fn intern_font(font: Cow<'static, [u8]>) -> &'static [u8] {
+    let mut fonts = FONTS.lock().unwrap();
+    if let Some(interned) = fonts.iter().find(|interned| **interned == *font) {
+        return interned;
+    }
+    let interned = match font {
+        Cow::Borrowed(bytes) => bytes,
+        Cow::Owned(bytes) => Box::leak(bytes.into_boxed_slice()),
+    };
+    fonts.push(interned);
+    interned
+}
+
+pub(crate) fn register_font_assets(
+    mut events: EventReader<AssetEvent<IcedFont>>,
+    assets: Res<Assets<IcedFont>>,
+    mut fonts: ResMut<IcedFonts>,
+) {
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                let Some(font) = assets.get(handle) else { continue };
+                let font = fonts.register(Cow::Owned(font.bytes.to_vec()));
+                fonts.assets.insert(handle.id(), font);
+            }
+            AssetEvent::Removed { handle } => {
+                fonts.assets.remove(&handle.id());
+            }
+        }
+    }
+}
+
+/// Shows Bevy [`Image`] assets in Iced `image` widgets.
+///
+/// Add this as a parameter to your UI system. The pixels of each image are copied once and
+/// copied again when the asset changes; images that only exist on the GPU, like camera render
+/// targets, can't be shown.
+/// ```ignore
+/// fn ui_system(mut images: IcedImages, icons: Res<Icons>, mut ctx: IcedContext<UiMessage>) {
+///     if let Some(sword) = images.handle(&icons.sword) {
+///         ctx.display(image(sword));
+///     }
+/// }
+/// ```
+#[cfg(feature = "image")]
+#[derive(SystemParam)]
+pub struct IcedImages<'w> {
+    images: Res<'w, Assets<Image>>,
+    cache: ResMut<'w, IcedImageCache>,
+}
+
+#[cfg(feature = "image")]
+impl<'w> IcedImages<'w> {
+    /// The Iced image handle of a Bevy image, or `None` while it is loading or if its format
+    /// can't be converted to RGBA.
+    pub fn handle(&mut self, handle: &bevy_asset::Handle<Image>) -> Option<image::Handle> {
+        if let Some(image) = self.cache.0.get(&handle.id()) {
+            return Some(image.clone());
+        }
+        let image = to_iced_image(self.images.get(handle)?)?;
+        self.cache.0.insert(handle.id(), image.clone());
+        Some(image)
+    }
+}
+
+#[cfg(feature = "image")]
+fn to_iced_image(image: &Image) -> Option<image::Handle> {
+    let size = image.texture_descriptor.size;
+    let pixels = match image.texture_descriptor.format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => image.data.clone(),
+        _ => image.convert(TextureFormat::Rgba8UnormSrgb)?.data,
+    };
+    Some(image::Handle::from_pixels(size.width, size.height, pixels))
+}
+
+#[doc(hidden)]
+#[cfg(feature = "image")]
+#[derive(Resource, Default)]
+pub struct IcedImageCache(HashMap<HandleId, image::Handle>);
+
+// The converted images are dropped with the caches of the renderers.
+#[cfg(feature = "image")]
+pub(crate) fn trim_images(trim: Res<TrimCaches>, mut cache: ResMut<IcedImageCache>) {
+    if **trim {
+        cache.0.clear();
+    }
+}
+
+#[cfg(feature = "image")]
+pub(crate) fn invalidate_images(
+    mut events: EventReader<AssetEvent<Image>>,
+    mut cache: ResMut<IcedImageCache>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Modified { handle } | AssetEvent::Removed { handle } = event {
+            cache.0.remove(&handle.id());
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use iced_native::event::{self, Event};
+use iced_native::layout::{self, Layout};
+use iced_native::widget::{Operation, Tree};
+use iced_native::{
+    mouse, overlay, renderer, Clipboard, Element, Length, Point, Rectangle, Shell, Size, Widget,
+};
+
+// Many small elements laid out independently, each with its top-left corner at its position,
+// so that they're built, laid out and drawn as a single interface.
+pub(crate) struct Batch<'a, M, Renderer> {
+    positions: Vec<Point>,
+    children: Vec<Element<'a, M, Renderer>>,
+}
+
+impl<'a, M, Renderer> FromIterator<(Point, Element<'a, M, Renderer>)> for Batch<'a, M, Renderer> {
+    fn from_iter<I: IntoIterator<Item = (Point, Element<'a, M, Renderer>)>>(iter: I) -> Self {
+        let (positions, children) = iter.into_iter().unzip();
+        Self {
+            positions,
+            children,
+        }
+    }
+}
+
+impl<'a, M, Renderer: iced_native::Renderer> Widget<M, Renderer> for Batch<'a, M, Renderer> {
+    fn width(&self) -> Length {
+        Length::Fill
+    }
+
+    fn height(&self) -> Length {
+        Length::Fill
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        self.children.iter().map(Tree::new).collect()
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(&self.children);
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        // Each element may take the whole UI, but is usually only as large as its content.
+        let child_limits = layout::Limits::new(Size::ZERO, limits.max());
+        let children = self
+            .children
+            .iter()
+            .zip(&self.positions)
+            .map(|(child, position)| {
+                let mut node = child.as_widget().layout(renderer, &child_limits);
+                node.move_to(*position);
+                node
+            })
+            .collect();
+        layout::Node::with_children(limits.max(), children)
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn Operation<M>,
+    ) {
+        operation.container(None, &mut |operation| {
+            self.children
+                .iter()
+                .zip(&mut tree.children)
+                .zip(layout.children())
+                .for_each(|((child, state), layout)| {
+                    child
+                        .as_widget()
+                        .operate(state, layout, renderer, operation);
+                });
+        });
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, M>,
+    ) -> event::Status {
+        self.children
+            .iter_mut()
+            .zip(&mut tree.children)
+            .zip(layout.children())
+            .map(|((child, state), layout)| {
+                child.as_widget_mut().on_event(
+                    state,
+                    event.clone(),
+                    layout,
+                    cursor_position,
+                    renderer,
+                    clipboard,
+                    shell,
+                )
+            })
+            .fold(event::Status::Ignored, event::Status::merge)
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.children
+            .iter()
+            .zip(&tree.children)
+            .zip(layout.children())
+            .map(|((child, state), layout)| {
+                child.as_widget().mouse_interaction(
+                    state,
+                    layout,
+                    cursor_position,
+                    viewport,
+                    renderer,
+                )
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Renderer::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        for ((child, state), layout) in self
+            .children
+            .iter()
+            .zip(&tree.children)
+            .zip(layout.children())
+        {
+            // Elements outside of the UI, e.g. above entities off screen, aren't drawn.
+            if layout.bounds().intersection(viewport).is_some() {
+                child.as_widget().draw(
+                    state,
+                    renderer,
+                    theme,
+                    style,
+                    layout,
+                    cursor_position,
+                    viewport,
+                );
+            }
+        }
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+    ) -> Option<overlay::Element<'b, M, Renderer>> {
+        overlay::from_children(&mut self.children, tree, layout, renderer)
+    }
+}
+
+impl<'a, M: 'a, Renderer: iced_native::Renderer + 'a> From<Batch<'a, M, Renderer>>
+    for Element<'a, M, Renderer>
+{
+    fn from(batch: Batch<'a, M, Renderer>) -> Self {
+        Element::new(batch)
+    }
+}
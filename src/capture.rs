@@ -0,0 +1,223 @@
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+
+use bevy_asset::{Assets, Handle};
+use bevy_derive::{Deref, DerefMut};
+use bevy_ecs::prelude::{Commands, Component, Entity, Query, Res, ResMut, With};
+use bevy_ecs::system::Resource;
+use bevy_render::color::Color;
+use bevy_render::render_resource::{
+    Buffer, Extent3d, TextureDimension, TextureFormat, TextureUsages,
+};
+use bevy_render::renderer::RenderDevice;
+use bevy_render::texture::Image;
+use bevy_utils::HashMap;
+use iced_wgpu::wgpu;
+
+use crate::render::{IcedFrames, ViewportResource};
+use crate::{IcedClearColor, IcedIdle, IcedRenderTarget};
+
+/// A capture of the UIs of a window, without what the cameras rendered, started by
+/// [`IcedContext::capture_to_image`](crate::IcedContext::capture_to_image).
+///
+/// The UIs displayed in the frame of the call are drawn into a new image at the physical size
+/// of the window, over a transparent background. The image can be shown in Bevy as soon as the
+/// frame ends, and its pixels are read back from the GPU a few frames later, e.g. to save a
+/// screenshot or compare it in a visual test:
+/// ```ignore
+/// fn save_capture(capture: Option<Res<UiCapture>>) {
+///     let Some(pixels) = capture.and_then(|capture| capture.0.take_pixels()) else { return };
+///     pixels.try_into_dynamic().unwrap().save("ui.png").unwrap();
+/// }
+/// ```
+///
+/// Nothing is captured while the window is minimized.
+#[derive(Clone, Default)]
+pub struct IcedCapture(Arc<Mutex<CaptureState>>);
+
+#[derive(Default)]
+struct CaptureState {
+    image: Option<Handle<Image>>,
+    pixels: Option<Image>,
+}
+
+impl IcedCapture {
+    /// The image that the UIs are drawn into, once the capture started at the end of its frame.
+    pub fn image(&self) -> Option<Handle<Image>> {
+        self.0.lock().unwrap().image.clone()
+    }
+
+    /// Takes the captured pixels, in an `Rgba8UnormSrgb` image, once they're read back.
+    pub fn take_pixels(&self) -> Option<Image> {
+        self.0.lock().unwrap().pixels.take()
+    }
+}
+
+// The captures requested this frame, and the windows they capture.
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct IcedCaptureRequests(Vec<(Entity, IcedCapture)>);
+
+// A render target that a capture is drawn into, for the frame it was requested in.
+#[derive(Component)]
+pub(crate) struct CaptureTarget(IcedCapture);
+
+/// Draws the UIs of each captured window into a new image, through a render target that's
+/// cleared to transparent and given a copy of the window's layers.
+///
+/// Runs before the asset events of the frame, so the image is on the GPU by the time the UIs
+/// are rendered.
+pub(crate) fn start_captures(
+    mut commands: Commands,
+    mut requests: ResMut<IcedCaptureRequests>,
+    mut frames: ResMut<IcedFrames>,
+    mut viewports: ResMut<ViewportResource>,
+    mut images: ResMut<Assets<Image>>,
+    idle: Res<IcedIdle>,
+) {
+    // No UI is displayed while idle, so the requests wait until the UIs are displayed again.
+    if idle.is_idle() {
+        return;
+    }
+    for (window, capture) in requests.drain(..) {
+        let Some(viewport) = viewports.get(&window).cloned() else { continue };
+        let size = viewport.physical_size();
+        let mut image = Image::new_fill(
+            Extent3d {
+                width: size.width,
+                height: size.height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[0, 0, 0, 0],
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_DST
+            | TextureUsages::COPY_SRC
+            | TextureUsages::RENDER_ATTACHMENT;
+        let image = images.add(image);
+        capture.0.lock().unwrap().image = Some(image.clone());
+
+        let target = commands
+            .spawn((
+                IcedRenderTarget(image),
+                IcedClearColor(Color::NONE),
+                CaptureTarget(capture),
+            ))
+            .id();
+        let layers = frames.get(&window).cloned().unwrap_or_default();
+        frames.insert(target, layers);
+        viewports.insert(target, viewport);
+    }
+}
+
+/// Despawns the render targets of the captures drawn in the last frame. The images are kept
+/// for as long as the captures are.
+pub(crate) fn end_captures(mut commands: Commands, targets: Query<Entity, With<CaptureTarget>>) {
+    for target in targets.iter() {
+        commands.entity(target).despawn();
+    }
+}
+
+// The captures drawn this frame, by render target.
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct ExtractedCaptures(HashMap<Entity, IcedCapture>);
+
+impl ExtractedCaptures {
+    pub(crate) fn extract(targets: &Query<(Entity, &CaptureTarget)>) -> Self {
+        Self(
+            targets
+                .iter()
+                .map(|(entity, CaptureTarget(capture))| (entity, capture.clone()))
+                .collect(),
+        )
+    }
+}
+
+// The copies of captured images into buffers, waiting for the frame to be submitted before
+// the buffers can be mapped.
+#[derive(Resource, Default)]
+pub(crate) struct PendingReadbacks(Mutex<Vec<Readback>>);
+
+struct Readback {
+    buffer: Buffer,
+    size: Extent3d,
+    padded_bytes_per_row: u32,
+    capture: IcedCapture,
+}
+
+impl PendingReadbacks {
+    // Copies the texture of a capture into a new buffer, with rows padded to the alignment
+    // wgpu requires.
+    pub(crate) fn copy(
+        &self,
+        device: &RenderDevice,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        size: Extent3d,
+        capture: IcedCapture,
+    ) {
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded_bytes_per_row = (size.width * 4 + align - 1) / align * align;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("iced_capture_buffer"),
+            size: padded_bytes_per_row as u64 * size.height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        self.0.lock().unwrap().push(Readback {
+            buffer,
+            size,
+            padded_bytes_per_row,
+            capture,
+        });
+    }
+}
+
+/// Maps the buffers copied this frame, which has been submitted by now, and hands their pixels
+/// to the captures once the GPU is done with them.
+pub(crate) fn map_readbacks(readbacks: Res<PendingReadbacks>, device: Res<RenderDevice>) {
+    for readback in readbacks.0.lock().unwrap().drain(..) {
+        let Readback {
+            buffer,
+            size,
+            padded_bytes_per_row,
+            capture,
+        } = readback;
+        let mapped = buffer.clone();
+        buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                if result.is_err() {
+                    return;
+                }
+                let data = mapped.slice(..).get_mapped_range();
+                let pixels = data
+                    .chunks(padded_bytes_per_row as usize)
+                    .flat_map(|row| &row[..size.width as usize * 4])
+                    .copied()
+                    .collect();
+                drop(data);
+                mapped.unmap();
+                capture.0.lock().unwrap().pixels = Some(Image::new(
+                    size,
+                    TextureDimension::D2,
+                    pixels,
+                    TextureFormat::Rgba8UnormSrgb,
+                ));
+            });
+    }
+    device.wgpu_device().poll(wgpu::Maintain::Poll);
+}
@@ -0,0 +1,126 @@
+use bevy_app::App;
+use iced_native::clipboard::Clipboard;
+#[cfg(all(feature = "clipboard", not(target_arch = "wasm32")))]
+use std::sync::Mutex;
+
+#[cfg(all(feature = "clipboard", target_arch = "wasm32"))]
+pub(crate) use web::*;
+
+pub(crate) type BoxedClipboard = Box<dyn Clipboard + Send>;
+
+#[cfg(all(feature = "clipboard", not(target_arch = "wasm32")))]
+pub(crate) fn default_clipboard(_app: &mut App) -> BoxedClipboard {
+    Box::new(SystemClipboard::new())
+}
+
+#[cfg(all(feature = "clipboard", target_arch = "wasm32"))]
+pub(crate) fn default_clipboard(app: &mut App) -> BoxedClipboard {
+    use bevy_ecs::schedule::IntoSystemConfig;
+
+    let pasted = PastedText::default();
+    app.insert_resource(pasted.clone()).add_system(
+        deliver_pasted_text
+            .after(crate::systems::process_input)
+            .in_set(crate::IcedSet::ProcessInput),
+    );
+    Box::new(WebClipboard { pasted })
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub(crate) fn default_clipboard(_app: &mut App) -> BoxedClipboard {
+    Box::new(iced_native::clipboard::Null)
+}
+
+/// The system clipboard, accessed through `arboard`.
+#[cfg(all(feature = "clipboard", not(target_arch = "wasm32")))]
+pub(crate) struct SystemClipboard(Option<Mutex<arboard::Clipboard>>);
+
+#[cfg(all(feature = "clipboard", not(target_arch = "wasm32")))]
+impl SystemClipboard {
+    pub fn new() -> Self {
+        Self(arboard::Clipboard::new().ok().map(Mutex::new))
+    }
+}
+
+#[cfg(all(feature = "clipboard", not(target_arch = "wasm32")))]
+impl Clipboard for SystemClipboard {
+    fn read(&self) -> Option<String> {
+        self.0.as_ref()?.lock().ok()?.get_text().ok()
+    }
+
+    fn write(&mut self, contents: String) {
+        if let Some(clipboard) = self.0.as_mut() {
+            let _ = clipboard.get_mut().unwrap().set_text(contents);
+        }
+    }
+}
+
+/// The browser clipboard.
+///
+/// Reading the browser clipboard is asynchronous, so a paste can't be answered immediately.
+/// Instead, the text is read in the background and typed into the focused window on a
+/// following frame.
+#[cfg(all(feature = "clipboard", target_arch = "wasm32"))]
+mod web {
+    use std::sync::{Arc, Mutex};
+
+    use bevy_ecs::prelude::{Entity, Query, Res, ResMut, Resource, With};
+    use bevy_window::{PrimaryWindow, Window};
+    use iced_native::clipboard::Clipboard;
+    use iced_native::{keyboard, Event as IcedEvent};
+    use wasm_bindgen_futures::JsFuture;
+
+    use crate::systems::{focused_window, IcedEventQueue};
+
+    // Text read from the browser clipboard that hasn't been delivered yet.
+    #[derive(Resource, Clone, Default)]
+    pub(crate) struct PastedText(Arc<Mutex<Vec<String>>>);
+
+    pub(crate) struct WebClipboard {
+        pub pasted: PastedText,
+    }
+
+    fn browser_clipboard() -> Option<web_sys::Clipboard> {
+        web_sys::window()?.navigator().clipboard()
+    }
+
+    impl Clipboard for WebClipboard {
+        fn read(&self) -> Option<String> {
+            let clipboard = browser_clipboard()?;
+            let pasted = self.pasted.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Ok(text) = JsFuture::from(clipboard.read_text()).await {
+                    if let Some(text) = text.as_string() {
+                        pasted.0.lock().unwrap().push(text);
+                    }
+                }
+            });
+            None
+        }
+
+        fn write(&mut self, contents: String) {
+            if let Some(clipboard) = browser_clipboard() {
+                let _ = clipboard.write_text(&contents);
+            }
+        }
+    }
+
+    pub(crate) fn deliver_pasted_text(
+        pasted: Res<PastedText>,
+        windows: Query<(Entity, &Window)>,
+        primary_window: Query<Entity, With<PrimaryWindow>>,
+        mut event_queue: ResMut<IcedEventQueue>,
+    ) {
+        let mut pasted = pasted.0.lock().unwrap();
+        let Some(window) = focused_window(&windows, &primary_window) else { return };
+
+        for text in pasted.drain(..) {
+            for c in text.chars() {
+                event_queue.push(
+                    window,
+                    IcedEvent::Keyboard(keyboard::Event::CharacterReceived(c)),
+                );
+            }
+        }
+    }
+}
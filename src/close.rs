@@ -0,0 +1,85 @@
+use bevy_ecs::prelude::{Commands, Entity, EventReader, Query, ResMut, Resource, With};
+use bevy_utils::HashSet;
+use bevy_window::{Window, WindowCloseRequested};
+use iced_native::{window, Event as IcedEvent};
+
+use crate::systems::IcedEventQueue;
+
+/// The requests to close windows that wait for the UIs to confirm or cancel them, e.g. after
+/// asking about unsaved changes.
+///
+/// Every request is sent to the UIs of the window as a `window::Event::CloseRequested`, and
+/// stays pending here until [`confirm`](Self::confirm) closes the window or
+/// [`cancel`](Self::cancel) keeps it open:
+/// ```ignore
+/// fn handle_messages(
+///     mut messages: EventReader<UiMessage>,
+///     mut close: ResMut<IcedCloseRequests>,
+/// ) {
+///     for message in messages.iter() {
+///         match message {
+///             UiMessage::QuitConfirmed(window) => close.confirm(*window),
+///             UiMessage::QuitCancelled(window) => close.cancel(*window),
+///         }
+///     }
+/// }
+/// ```
+///
+/// Bevy closes windows as soon as they are requested to close, so disable `close_when_requested`
+/// in its `WindowPlugin` to let the UIs cancel requests. Otherwise they are only told about them.
+#[derive(Resource, Default)]
+pub struct IcedCloseRequests {
+    pending: HashSet<Entity>,
+    confirmed: HashSet<Entity>,
+}
+
+impl IcedCloseRequests {
+    /// Whether the window was requested to close, and the request wasn't confirmed or cancelled
+    /// yet.
+    pub fn is_requested(&self, window: Entity) -> bool {
+        self.pending.contains(&window)
+    }
+
+    /// The windows requested to close.
+    pub fn requested(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.pending.iter().copied()
+    }
+
+    /// Closes the window at the end of the frame.
+    pub fn confirm(&mut self, window: Entity) {
+        self.pending.remove(&window);
+        self.confirmed.insert(window);
+    }
+
+    /// Keeps the window open.
+    pub fn cancel(&mut self, window: Entity) {
+        self.pending.remove(&window);
+    }
+}
+
+/// Tells the UIs about the requests to close their windows.
+pub(crate) fn receive_close_requests(
+    mut close_requested: EventReader<WindowCloseRequested>,
+    mut requests: ResMut<IcedCloseRequests>,
+    mut event_queue: ResMut<IcedEventQueue>,
+) {
+    for ev in close_requested.iter() {
+        requests.pending.insert(ev.window);
+        event_queue.push(ev.window, IcedEvent::Window(window::Event::CloseRequested));
+    }
+}
+
+/// Despawns the windows whose close requests were confirmed, like Bevy does when closing them.
+pub(crate) fn close_confirmed_windows(
+    mut commands: Commands,
+    mut requests: ResMut<IcedCloseRequests>,
+    windows: Query<Entity, With<Window>>,
+) {
+    let IcedCloseRequests { pending, confirmed } = &mut *requests;
+    for window in confirmed.drain() {
+        if windows.contains(window) {
+            commands.entity(window).despawn();
+        }
+    }
+    pending.retain(|window| windows.contains(*window));
+}
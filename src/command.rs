@@ -0,0 +1,91 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+use bevy_tasks::AsyncComputeTaskPool;
+use iced_native::widget::operation::{Operation, Outcome};
+use iced_native::UserInterface;
+
+// The futures of commands, as defined by `iced_futures`.
+#[cfg(not(target_arch = "wasm32"))]
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+#[cfg(target_arch = "wasm32")]
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T>>>;
+
+// The unfinished work of the commands performed for one message type.
+pub(crate) struct CommandQueue<M> {
+    sender: Sender<M>,
+    receiver: Receiver<M>,
+    operations: Vec<Box<dyn Operation<M>>>,
+    // The buffer that display calls collect messages in, kept to reuse its allocation.
+    messages: Vec<M>,
+}
+
+impl<M> Default for CommandQueue<M> {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver,
+            operations: Vec::new(),
+            messages: Vec::new(),
+        }
+    }
+}
+
+impl<M: Send + 'static> CommandQueue<M> {
+    /// Runs a future on the async compute task pool, queueing its message.
+    pub(crate) fn spawn(&self, future: BoxFuture<M>) {
+        let sender = self.sender.clone();
+        AsyncComputeTaskPool::get()
+            .spawn(async move {
+                // The receiver only goes away with the app.
+                let _ = sender.send(future.await);
+            })
+            .detach();
+    }
+
+    /// Queues a widget operation to run the next time the UI is displayed.
+    pub(crate) fn push_operation(&mut self, operation: Box<dyn Operation<M>>) {
+        self.operations.push(operation);
+    }
+
+    /// The messages of the futures that finished so far.
+    pub(crate) fn finished(&self) -> impl Iterator<Item = M> + '_ {
+        self.receiver.try_iter()
+    }
+
+    pub(crate) fn take_operations(&mut self) -> Vec<Box<dyn Operation<M>>> {
+        std::mem::take(&mut self.operations)
+    }
+
+    /// An empty buffer for the messages of a display call, to give back with
+    /// [`reuse_messages`](Self::reuse_messages).
+    pub(crate) fn take_messages(&mut self) -> Vec<M> {
+        let mut messages = std::mem::take(&mut self.messages);
+        messages.clear();
+        messages
+    }
+
+    pub(crate) fn reuse_messages(&mut self, messages: Vec<M>) {
+        self.messages = messages;
+    }
+}
+
+/// Runs a widget operation, and the operations chained to it, on the UI.
+pub(crate) fn run_operation<M, Renderer: iced_native::Renderer>(
+    ui: &mut UserInterface<'_, M, Renderer>,
+    renderer: &Renderer,
+    operation: Box<dyn Operation<M>>,
+    messages: &mut Vec<M>,
+) {
+    let mut current = Some(operation);
+    while let Some(mut operation) = current.take() {
+        ui.operate(renderer, operation.as_mut());
+        match operation.finish() {
+            Outcome::None => {}
+            Outcome::Some(message) => messages.push(message),
+            Outcome::Chain(next) => current = Some(next),
+        }
+    }
+}
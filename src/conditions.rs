@@ -0,0 +1,21 @@
+use bevy_ecs::system::Res;
+
+use crate::IcedDisplayResult;
+
+/// A run condition that is true while the cursor is over an element of the UI.
+///
+/// ```ignore
+/// app.add_system(camera_controller.run_if(not(pointer_over_ui())));
+/// ```
+pub fn pointer_over_ui() -> impl FnMut(Res<IcedDisplayResult>) -> bool + Clone {
+    |result: Res<IcedDisplayResult>| result.wants_pointer_input
+}
+
+/// A run condition that is true while a widget of the UI, like a text input, has keyboard focus.
+///
+/// ```ignore
+/// app.add_system(player_movement.run_if(not(keyboard_captured())));
+/// ```
+pub fn keyboard_captured() -> impl FnMut(Res<IcedDisplayResult>) -> bool + Clone {
+    |result: Res<IcedDisplayResult>| result.wants_keyboard_input
+}
@@ -1,16 +1,20 @@
+//! Conversions from Bevy input types to Iced ones, for custom event bridges that build Iced
+//! events themselves, e.g. to pass to [`IcedContext::push_event`](crate::IcedContext::push_event).
+
 use bevy_input::prelude::KeyCode as BevyKeyCode;
 use bevy_input::prelude::MouseButton;
 #[cfg(feature = "touch")]
 use bevy_input::touch::{TouchInput, TouchPhase};
-#[cfg(feature = "touch")]
-use bevy_math::Vec2;
+use bevy_window::CursorIcon;
 use iced_native::keyboard::KeyCode as IcedKeyCode;
+use iced_native::mouse::Interaction;
 #[cfg(feature = "touch")]
 use iced_native::{
     touch::{self, Finger},
     Point,
 };
 
+/// The Iced key code of a Bevy key code. Every Bevy key has an Iced equivalent.
 pub fn key_code(virtual_keycode: BevyKeyCode) -> IcedKeyCode {
     match virtual_keycode {
         BevyKeyCode::Key1 => IcedKeyCode::Key1,
@@ -179,54 +183,169 @@ pub fn key_code(virtual_keycode: BevyKeyCode) -> IcedKeyCode {
     }
 }
 
-pub fn mouse_button(button: MouseButton) -> iced_native::mouse::Button {
+/// The key at the position of a scan code on a US keyboard, for the keys that move between
+/// layouts. Scan codes are only known on platforms that report PC scan codes.
+pub fn physical_key_code(scan_code: u32) -> Option<IcedKeyCode> {
+    if !cfg!(any(
+        target_os = "windows",
+        target_os = "linux",
+        target_os = "freebsd"
+    )) {
+        return None;
+    }
+    let key_code = match scan_code {
+        0x02 => IcedKeyCode::Key1,
+        0x03 => IcedKeyCode::Key2,
+        0x04 => IcedKeyCode::Key3,
+        0x05 => IcedKeyCode::Key4,
+        0x06 => IcedKeyCode::Key5,
+        0x07 => IcedKeyCode::Key6,
+        0x08 => IcedKeyCode::Key7,
+        0x09 => IcedKeyCode::Key8,
+        0x0A => IcedKeyCode::Key9,
+        0x0B => IcedKeyCode::Key0,
+        0x0C => IcedKeyCode::Minus,
+        0x0D => IcedKeyCode::Equals,
+        0x10 => IcedKeyCode::Q,
+        0x11 => IcedKeyCode::W,
+        0x12 => IcedKeyCode::E,
+        0x13 => IcedKeyCode::R,
+        0x14 => IcedKeyCode::T,
+        0x15 => IcedKeyCode::Y,
+        0x16 => IcedKeyCode::U,
+        0x17 => IcedKeyCode::I,
+        0x18 => IcedKeyCode::O,
+        0x19 => IcedKeyCode::P,
+        0x1A => IcedKeyCode::LBracket,
+        0x1B => IcedKeyCode::RBracket,
+        0x1E => IcedKeyCode::A,
+        0x1F => IcedKeyCode::S,
+        0x20 => IcedKeyCode::D,
+        0x21 => IcedKeyCode::F,
+        0x22 => IcedKeyCode::G,
+        0x23 => IcedKeyCode::H,
+        0x24 => IcedKeyCode::J,
+        0x25 => IcedKeyCode::K,
+        0x26 => IcedKeyCode::L,
+        0x27 => IcedKeyCode::Semicolon,
+        0x28 => IcedKeyCode::Apostrophe,
+        0x29 => IcedKeyCode::Grave,
+        0x2B => IcedKeyCode::Backslash,
+        0x2C => IcedKeyCode::Z,
+        0x2D => IcedKeyCode::X,
+        0x2E => IcedKeyCode::C,
+        0x2F => IcedKeyCode::V,
+        0x30 => IcedKeyCode::B,
+        0x31 => IcedKeyCode::N,
+        0x32 => IcedKeyCode::M,
+        0x33 => IcedKeyCode::Comma,
+        0x34 => IcedKeyCode::Period,
+        0x35 => IcedKeyCode::Slash,
+        _ => return None,
+    };
+    Some(key_code)
+}
+
+/// The letter or digit key that types a character in the current layout.
+pub fn char_key_code(c: char) -> Option<IcedKeyCode> {
+    let key_code = match c.to_ascii_lowercase() {
+        'a' => IcedKeyCode::A,
+        'b' => IcedKeyCode::B,
+        'c' => IcedKeyCode::C,
+        'd' => IcedKeyCode::D,
+        'e' => IcedKeyCode::E,
+        'f' => IcedKeyCode::F,
+        'g' => IcedKeyCode::G,
+        'h' => IcedKeyCode::H,
+        'i' => IcedKeyCode::I,
+        'j' => IcedKeyCode::J,
+        'k' => IcedKeyCode::K,
+        'l' => IcedKeyCode::L,
+        'm' => IcedKeyCode::M,
+        'n' => IcedKeyCode::N,
+        'o' => IcedKeyCode::O,
+        'p' => IcedKeyCode::P,
+        'q' => IcedKeyCode::Q,
+        'r' => IcedKeyCode::R,
+        's' => IcedKeyCode::S,
+        't' => IcedKeyCode::T,
+        'u' => IcedKeyCode::U,
+        'v' => IcedKeyCode::V,
+        'w' => IcedKeyCode::W,
+        'x' => IcedKeyCode::X,
+        'y' => IcedKeyCode::Y,
+        'z' => IcedKeyCode::Z,
+        '1' => IcedKeyCode::Key1,
+        '2' => IcedKeyCode::Key2,
+        '3' => IcedKeyCode::Key3,
+        '4' => IcedKeyCode::Key4,
+        '5' => IcedKeyCode::Key5,
+        '6' => IcedKeyCode::Key6,
+        '7' => IcedKeyCode::Key7,
+        '8' => IcedKeyCode::Key8,
+        '9' => IcedKeyCode::Key9,
+        '0' => IcedKeyCode::Key0,
+        _ => return None,
+    };
+    Some(key_code)
+}
+
+/// The Iced button that the back side button of a mouse is reported as, on every platform.
+pub const BACK_BUTTON: iced_native::mouse::Button = iced_native::mouse::Button::Other(3);
+
+/// The Iced button that the forward side button of a mouse is reported as, on every platform.
+pub const FORWARD_BUTTON: iced_native::mouse::Button = iced_native::mouse::Button::Other(4);
+
+/// The Iced mouse button of a Bevy mouse button, or `None` for an extra button whose number
+/// doesn't fit in Iced's.
+///
+/// The side buttons, which each platform numbers differently, are converted to
+/// [`BACK_BUTTON`] and [`FORWARD_BUTTON`]. Other extra buttons keep their number.
+pub fn mouse_button(button: MouseButton) -> Option<iced_native::mouse::Button> {
     use iced_native::mouse::Button;
-    match button {
+    // The numbers of the back and forward buttons reported by the platform. On Linux, they are
+    // X11 button numbers or evdev codes (`BTN_SIDE` and `BTN_EXTRA`) depending on the backend.
+    let (back, forward): (&[u16], &[u16]) = if cfg!(target_os = "windows") {
+        (&[1], &[2])
+    } else if cfg!(any(target_os = "linux", target_os = "freebsd")) {
+        (&[8, 275], &[9, 276])
+    } else {
+        (&[3], &[4])
+    };
+    let button = match button {
         MouseButton::Left => Button::Left,
         MouseButton::Right => Button::Right,
         MouseButton::Middle => Button::Middle,
-        MouseButton::Other(val) => Button::Other(val as u8),
+        MouseButton::Other(val) if back.contains(&val) => BACK_BUTTON,
+        MouseButton::Other(val) if forward.contains(&val) => FORWARD_BUTTON,
+        MouseButton::Other(val) => Button::Other(u8::try_from(val).ok()?),
+    };
+    Some(button)
+}
+
+/// The Bevy cursor icon shown for the mouse interaction of an Iced widget.
+pub fn cursor_icon(interaction: Interaction) -> CursorIcon {
+    match interaction {
+        Interaction::Idle => CursorIcon::Default,
+        Interaction::Pointer => CursorIcon::Hand,
+        Interaction::Grab => CursorIcon::Grab,
+        Interaction::Text => CursorIcon::Text,
+        Interaction::Crosshair => CursorIcon::Crosshair,
+        Interaction::Working => CursorIcon::Progress,
+        Interaction::Grabbing => CursorIcon::Grabbing,
+        Interaction::ResizingHorizontally => CursorIcon::EwResize,
+        Interaction::ResizingVertically => CursorIcon::NsResize,
     }
 }
 
+/// Converts a touch input, whose position has already been mapped into UI coordinates.
 #[cfg(feature = "touch")]
-pub fn touch_event(bevy_touch_input: &TouchInput) -> touch::Event {
-    match *bevy_touch_input {
-        TouchInput {
-            phase: TouchPhase::Started,
-            position: Vec2 { x, y },
-            id: finger,
-            ..
-        } => touch::Event::FingerPressed {
-            id: Finger(finger),
-            position: Point { x, y },
-        },
-        TouchInput {
-            phase: TouchPhase::Cancelled,
-            position: Vec2 { x, y },
-            id: finger,
-            ..
-        } => touch::Event::FingerLost {
-            id: Finger(finger),
-            position: Point { x, y },
-        },
-        TouchInput {
-            phase: TouchPhase::Ended,
-            position: Vec2 { x, y },
-            id: finger,
-            ..
-        } => touch::Event::FingerLifted {
-            id: Finger(finger),
-            position: Point { x, y },
-        },
-        TouchInput {
-            phase: TouchPhase::Moved,
-            position: Vec2 { x, y },
-            id: finger,
-            ..
-        } => touch::Event::FingerMoved {
-            id: Finger(finger),
-            position: Point { x, y },
-        },
+pub fn touch_event(bevy_touch_input: &TouchInput, position: Point) -> touch::Event {
+    let id = Finger(bevy_touch_input.id);
+    match bevy_touch_input.phase {
+        TouchPhase::Started => touch::Event::FingerPressed { id, position },
+        TouchPhase::Cancelled => touch::Event::FingerLost { id, position },
+        TouchPhase::Ended => touch::Event::FingerLifted { id, position },
+        TouchPhase::Moved => touch::Event::FingerMoved { id, position },
     }
 }
@@ -0,0 +1,72 @@
+use std::hash::Hasher;
+
+use bevy_ecs::change_detection::{DetectChanges, Mut, NonSendMut, Ref, Res, ResMut};
+use bevy_ecs::system::Resource;
+
+/// Values with Bevy change detection that a UI is built from, see
+/// [`IcedContext::display_with`](crate::IcedContext::display_with).
+///
+/// Implemented for `Res`, `ResMut`, `NonSendMut`, `Ref` and `Mut`, references to them, and
+/// tuples of up to 8 dependencies.
+pub trait IcedDependencies {
+    /// Hashes when the values last changed, which differs from the last frame when any of them
+    /// changed since.
+    fn hash_changes(&self, state: &mut dyn Hasher);
+}
+
+impl<D: IcedDependencies + ?Sized> IcedDependencies for &D {
+    fn hash_changes(&self, state: &mut dyn Hasher) {
+        (**self).hash_changes(state);
+    }
+}
+
+impl<'w, T: Resource> IcedDependencies for Res<'w, T> {
+    fn hash_changes(&self, state: &mut dyn Hasher) {
+        state.write_u32(self.last_changed());
+    }
+}
+
+impl<'w, T: Resource> IcedDependencies for ResMut<'w, T> {
+    fn hash_changes(&self, state: &mut dyn Hasher) {
+        state.write_u32(self.last_changed());
+    }
+}
+
+impl<'w, T: 'static> IcedDependencies for NonSendMut<'w, T> {
+    fn hash_changes(&self, state: &mut dyn Hasher) {
+        state.write_u32(self.last_changed());
+    }
+}
+
+impl<'w, T: ?Sized> IcedDependencies for Ref<'w, T> {
+    fn hash_changes(&self, state: &mut dyn Hasher) {
+        state.write_u32(self.last_changed());
+    }
+}
+
+impl<'w, T: ?Sized> IcedDependencies for Mut<'w, T> {
+    fn hash_changes(&self, state: &mut dyn Hasher) {
+        state.write_u32(self.last_changed());
+    }
+}
+
+macro_rules! impl_tuple_dependencies {
+    ($($name:ident),*) => {
+        impl<$($name: IcedDependencies),*> IcedDependencies for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn hash_changes(&self, state: &mut dyn Hasher) {
+                let ($($name,)*) = self;
+                $($name.hash_changes(state);)*
+            }
+        }
+    };
+}
+
+impl_tuple_dependencies!(A);
+impl_tuple_dependencies!(A, B);
+impl_tuple_dependencies!(A, B, C);
+impl_tuple_dependencies!(A, B, C, D);
+impl_tuple_dependencies!(A, B, C, D, E);
+impl_tuple_dependencies!(A, B, C, D, E, F);
+impl_tuple_dependencies!(A, B, C, D, E, F, G);
+impl_tuple_dependencies!(A, B, C, D, E, F, G, H);
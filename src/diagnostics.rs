@@ -0,0 +1,214 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bevy_app::{App, CoreSet, Plugin};
+use bevy_diagnostic::{Diagnostic, DiagnosticId, Diagnostics};
+use bevy_ecs::schedule::IntoSystemConfig;
+use bevy_ecs::system::{Local, Res, ResMut, Resource};
+use bevy_render::RenderApp;
+use bevy_utils::HashSet;
+use iced_native::image;
+use iced_wgpu::Primitive;
+
+use crate::render::IcedFrames;
+use crate::IcedIdle;
+
+/// Adds diagnostics for the time spent on the UIs each frame, and the number of primitives
+/// they draw, so they can be logged with `LogDiagnosticsPlugin` or shown by other tools.
+/// More detailed counts are published in the [`IcedFrameStats`] resource.
+///
+/// Add it after [`IcedPlugin`](crate::IcedPlugin):
+/// ```ignore
+/// app.add_plugin(IcedPlugin::default())
+///     .add_plugin(IcedDiagnosticsPlugin)
+///     .add_plugin(LogDiagnosticsPlugin::default());
+/// ```
+#[derive(Default)]
+pub struct IcedDiagnosticsPlugin;
+
+impl IcedDiagnosticsPlugin {
+    /// Time spent building the layout of the UIs, in milliseconds.
+    pub const LAYOUT_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x5d3b2a4e_0c1f_4a8e_9b7d_2f6e1c8a4b01);
+    /// Time spent processing input and running widget operations, in milliseconds.
+    pub const UPDATE_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x5d3b2a4e_0c1f_4a8e_9b7d_2f6e1c8a4b02);
+    /// Time spent drawing the UIs into primitives, in milliseconds.
+    pub const DRAW_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x5d3b2a4e_0c1f_4a8e_9b7d_2f6e1c8a4b03);
+    /// Time spent by the render node presenting the primitives of the previous frame, in
+    /// milliseconds.
+    pub const RENDER_TIME: DiagnosticId =
+        DiagnosticId::from_u128(0x5d3b2a4e_0c1f_4a8e_9b7d_2f6e1c8a4b04);
+    /// Number of primitives drawn in all windows and render targets.
+    pub const PRIMITIVES: DiagnosticId =
+        DiagnosticId::from_u128(0x5d3b2a4e_0c1f_4a8e_9b7d_2f6e1c8a4b05);
+
+    fn setup(mut diagnostics: ResMut<Diagnostics>) {
+        let ms = |id, name| Diagnostic::new(id, name, 20).with_suffix("ms");
+        diagnostics.add(ms(Self::LAYOUT_TIME, "iced_layout_time"));
+        diagnostics.add(ms(Self::UPDATE_TIME, "iced_update_time"));
+        diagnostics.add(ms(Self::DRAW_TIME, "iced_draw_time"));
+        diagnostics.add(ms(Self::RENDER_TIME, "iced_render_time"));
+        diagnostics.add(Diagnostic::new(Self::PRIMITIVES, "iced_primitives", 20));
+    }
+
+    fn measure(
+        mut diagnostics: ResMut<Diagnostics>,
+        timings: Res<IcedTimings>,
+        render_time: Res<IcedRenderTime>,
+        frames: Res<IcedFrames>,
+    ) {
+        let ms = |duration: Duration| duration.as_secs_f64() * 1000.0;
+        diagnostics.add_measurement(Self::LAYOUT_TIME, || ms(timings.layout));
+        diagnostics.add_measurement(Self::UPDATE_TIME, || ms(timings.update));
+        diagnostics.add_measurement(Self::DRAW_TIME, || ms(timings.draw));
+        diagnostics.add_measurement(Self::RENDER_TIME, || ms(render_time.get()));
+        diagnostics.add_measurement(Self::PRIMITIVES, || {
+            frames
+                .values()
+                .flatten()
+                .flat_map(|layer| &layer.primitives)
+                .map(count_primitives)
+                .sum::<usize>() as f64
+        });
+    }
+}
+
+impl Plugin for IcedDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        let render_time = IcedRenderTime::default();
+        app.insert_resource(render_time.clone())
+            .init_resource::<IcedFrameStats>()
+            .add_startup_system(Self::setup)
+            .add_system(Self::measure.in_base_set(CoreSet::Last))
+            .add_system(update_frame_stats.in_base_set(CoreSet::Last));
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.insert_resource(render_time);
+        }
+    }
+}
+
+/// Statistics of the primitives that the UIs drew this frame, to profile their complexity and
+/// catch images that are uploaded again every frame.
+///
+/// Updated by [`IcedDiagnosticsPlugin`] at the end of every frame, unless the UIs are idle.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IcedFrameStats {
+    /// The windows and render targets that UIs were drawn in.
+    pub targets: usize,
+    /// The Iced layers, which are drawn with separate draw calls. Every target and every
+    /// clipped region, like the content of a scrollable, has one.
+    pub layers: usize,
+    /// The quads, like backgrounds and borders.
+    pub quads: usize,
+    /// The runs of text.
+    pub texts: usize,
+    /// The raster images.
+    pub images: usize,
+    /// The vector images.
+    pub svgs: usize,
+    /// The meshes, like canvas shapes, and other primitives.
+    pub other: usize,
+    /// The raster images that weren't drawn in the previous frame, which are uploaded to the
+    /// GPU. A steady count while nothing changes means images are created every frame.
+    pub uploaded_images: usize,
+    /// The size of the pixels or encoded files of those images, in bytes. Images loaded from
+    /// paths count as zero.
+    pub upload_bytes: usize,
+}
+
+impl IcedFrameStats {
+    fn count(&mut self, primitive: &Primitive, images: &mut HashSet<u64>, previous: &HashSet<u64>) {
+        match primitive {
+            Primitive::None => {}
+            Primitive::Group { primitives } => primitives
+                .iter()
+                .for_each(|primitive| self.count(primitive, images, previous)),
+            Primitive::Clip { content, .. } => {
+                self.layers += 1;
+                self.count(content, images, previous);
+            }
+            Primitive::Translate { content, .. } => self.count(content, images, previous),
+            Primitive::Cache { content } => self.count(content, images, previous),
+            Primitive::Quad { .. } => self.quads += 1,
+            Primitive::Text { .. } => self.texts += 1,
+            Primitive::Image { handle, .. } => {
+                self.images += 1;
+                if images.insert(handle.id()) && !previous.contains(&handle.id()) {
+                    self.uploaded_images += 1;
+                    self.upload_bytes += match handle.data() {
+                        image::Data::Rgba { pixels, .. } => pixels.len(),
+                        image::Data::Bytes(bytes) => bytes.len(),
+                        image::Data::Path(_) => 0,
+                    };
+                }
+            }
+            Primitive::Svg { .. } => self.svgs += 1,
+            _ => self.other += 1,
+        }
+    }
+}
+
+fn update_frame_stats(
+    mut stats: ResMut<IcedFrameStats>,
+    frames: Res<IcedFrames>,
+    idle: Res<IcedIdle>,
+    mut drawn_images: Local<HashSet<u64>>,
+) {
+    // The frames of idle UIs aren't rebuilt, but drawn again from the last active frame.
+    if idle.is_idle() {
+        return;
+    }
+    let mut images = HashSet::new();
+    let mut new_stats = IcedFrameStats {
+        targets: frames.len(),
+        layers: frames.len(),
+        ..Default::default()
+    };
+    for primitive in frames
+        .values()
+        .flatten()
+        .flat_map(|layer| &layer.primitives)
+    {
+        new_stats.count(primitive, &mut images, &drawn_images);
+    }
+    *stats = new_stats;
+    *drawn_images = images;
+}
+
+// The time spent on the UIs of the main world this frame.
+#[derive(Resource, Default)]
+pub(crate) struct IcedTimings {
+    pub(crate) layout: Duration,
+    pub(crate) update: Duration,
+    pub(crate) draw: Duration,
+}
+
+// The time the render node took in the last frame, written by the render world.
+#[derive(Resource, Clone, Default)]
+pub(crate) struct IcedRenderTime(Arc<AtomicU64>);
+
+impl IcedRenderTime {
+    pub(crate) fn set(&self, duration: Duration) {
+        self.0.store(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> Duration {
+        Duration::from_nanos(self.0.load(Ordering::Relaxed))
+    }
+}
+
+// Counts the primitives that are drawn, not including groups and other containers.
+fn count_primitives(primitive: &Primitive) -> usize {
+    match primitive {
+        Primitive::None => 0,
+        Primitive::Group { primitives } => primitives.iter().map(count_primitives).sum(),
+        Primitive::Clip { content, .. } | Primitive::Translate { content, .. } => {
+            count_primitives(content)
+        }
+        Primitive::Cache { content } => count_primitives(content),
+        _ => 1,
+    }
+}
@@ -0,0 +1,307 @@
+use bevy_ecs::prelude::{Entity, Local, Query, Res, ResMut, Resource, With};
+use bevy_input::gamepad::{
+    Gamepad, GamepadAxis, GamepadAxisType, GamepadButton, GamepadButtonType, Gamepads,
+};
+use bevy_input::{Axis, Input};
+use bevy_math::Vec2;
+use bevy_time::Time;
+use bevy_utils::HashMap;
+use bevy_window::{PrimaryWindow, Window};
+use iced_native::keyboard::{self, KeyCode, Modifiers};
+use iced_native::{mouse, renderer, Color, Event as IcedEvent, Point, Rectangle, Size};
+
+use crate::render::ViewportResource;
+use crate::systems::{focused_window, window_to_ui, IcedEventQueue};
+use crate::IcedCamera;
+
+/// A key press that gamepad input is translated into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NavigationKey {
+    /// The key that is pressed.
+    pub key_code: KeyCode,
+    /// The modifiers held while the key is pressed.
+    pub modifiers: Modifiers,
+}
+
+impl NavigationKey {
+    /// A key pressed without any modifiers.
+    pub const fn new(key_code: KeyCode) -> Self {
+        Self {
+            key_code,
+            modifiers: Modifiers::empty(),
+        }
+    }
+
+    /// A key pressed while holding `modifiers`.
+    pub const fn with_modifiers(key_code: KeyCode, modifiers: Modifiers) -> Self {
+        Self {
+            key_code,
+            modifiers,
+        }
+    }
+}
+
+/// Configures how gamepad input is translated into Iced keyboard navigation.
+///
+/// Requires the `gamepad` feature. Events are sent to the focused window, or to the
+/// [`IcedCamera`] the gamepad is assigned to.
+#[derive(Resource, Clone, Debug)]
+pub struct IcedGamepadNavigation {
+    /// The key pressed by each button.
+    pub buttons: HashMap<GamepadButtonType, NavigationKey>,
+    /// The keys pressed by each axis, in the negative and positive direction respectively.
+    pub axes: HashMap<GamepadAxisType, (NavigationKey, NavigationKey)>,
+    /// How far an axis has to be pushed before it presses a key.
+    pub axis_threshold: f32,
+}
+
+impl Default for IcedGamepadNavigation {
+    fn default() -> Self {
+        let next = NavigationKey::new(KeyCode::Tab);
+        let previous = NavigationKey::with_modifiers(KeyCode::Tab, Modifiers::SHIFT);
+        let left = NavigationKey::new(KeyCode::Left);
+        let right = NavigationKey::new(KeyCode::Right);
+
+        Self {
+            buttons: HashMap::from_iter([
+                (GamepadButtonType::DPadUp, previous),
+                (GamepadButtonType::DPadDown, next),
+                (GamepadButtonType::DPadLeft, left),
+                (GamepadButtonType::DPadRight, right),
+                (GamepadButtonType::South, NavigationKey::new(KeyCode::Enter)),
+                (GamepadButtonType::East, NavigationKey::new(KeyCode::Escape)),
+            ]),
+            axes: HashMap::from_iter([
+                (GamepadAxisType::LeftStickX, (left, right)),
+                (GamepadAxisType::LeftStickY, (next, previous)),
+            ]),
+            axis_threshold: 0.5,
+        }
+    }
+}
+
+fn key_event(key: NavigationKey, pressed: bool) -> IcedEvent {
+    let NavigationKey {
+        key_code,
+        modifiers,
+    } = key;
+    IcedEvent::Keyboard(if pressed {
+        keyboard::Event::KeyPressed {
+            key_code,
+            modifiers,
+        }
+    } else {
+        keyboard::Event::KeyReleased {
+            key_code,
+            modifiers,
+        }
+    })
+}
+
+pub(crate) fn process_gamepad_input(
+    config: Res<IcedGamepadNavigation>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    windows: Query<(Entity, &Window)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    cameras: Query<(Entity, &IcedCamera)>,
+    mut event_queue: ResMut<IcedEventQueue>,
+    // The key each axis is currently holding down.
+    mut held: Local<HashMap<(Gamepad, GamepadAxisType), NavigationKey>>,
+) {
+    let Some(focused) = focused_window(&windows, &primary_window) else { return };
+
+    for gamepad in gamepads.iter() {
+        let window = cameras
+            .iter()
+            .find(|(_, camera)| camera.gamepad == Some(gamepad))
+            .map_or(focused, |(camera, _)| camera);
+        for (&button_type, &key) in config.buttons.iter() {
+            let button = GamepadButton::new(gamepad, button_type);
+            if buttons.just_pressed(button) {
+                event_queue.push(window, key_event(key, true));
+            } else if buttons.just_released(button) {
+                event_queue.push(window, key_event(key, false));
+            }
+        }
+
+        for (&axis_type, &(negative, positive)) in config.axes.iter() {
+            let value = axes
+                .get(GamepadAxis::new(gamepad, axis_type))
+                .unwrap_or_default();
+            let key = if value <= -config.axis_threshold {
+                Some(negative)
+            } else if value >= config.axis_threshold {
+                Some(positive)
+            } else {
+                None
+            };
+
+            let previous = held.get(&(gamepad, axis_type)).copied();
+            if key == previous {
+                continue;
+            }
+            if let Some(previous) = previous {
+                event_queue.push(window, key_event(previous, false));
+                held.remove(&(gamepad, axis_type));
+            }
+            if let Some(key) = key {
+                event_queue.push(window, key_event(key, true));
+                held.insert((gamepad, axis_type), key);
+            }
+        }
+    }
+}
+
+/// A mouse cursor moved with a gamepad stick, so mouse-oriented UIs can be used with a controller.
+///
+/// Requires the `gamepad` feature. While enabled, the cursor replaces the mouse in the focused
+/// window and is drawn on top of the UI.
+#[derive(Resource, Clone, Debug)]
+pub struct IcedVirtualCursor {
+    /// Whether the virtual cursor is active.
+    pub enabled: bool,
+    /// The window the cursor is in. Follows the focused window.
+    pub window: Option<Entity>,
+    /// The position of the cursor, in logical window coordinates from the top-left corner.
+    pub position: Point,
+    /// How fast the cursor moves when the stick is fully pushed, in logical pixels per second.
+    pub speed: f32,
+    /// The horizontal and vertical axes that move the cursor.
+    pub axes: (GamepadAxisType, GamepadAxisType),
+    /// How far the stick has to be pushed before the cursor moves.
+    pub deadzone: f32,
+    /// The button that acts as the left mouse button.
+    pub button: GamepadButtonType,
+    /// The color the cursor is drawn with.
+    pub color: Color,
+    /// The diameter of the cursor, in logical pixels.
+    pub size: f32,
+}
+
+impl Default for IcedVirtualCursor {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: None,
+            position: Point::ORIGIN,
+            speed: 800.0,
+            axes: (GamepadAxisType::RightStickX, GamepadAxisType::RightStickY),
+            deadzone: 0.15,
+            button: GamepadButtonType::South,
+            color: Color::WHITE,
+            size: 12.0,
+        }
+    }
+}
+
+impl IcedVirtualCursor {
+    /// The position of the cursor in the UI space of `window`, if the cursor is in it.
+    pub(crate) fn ui_position(
+        &self,
+        window: Entity,
+        bevy_window: &Window,
+        bounds: Size,
+    ) -> Option<Point> {
+        if !self.enabled || self.window != Some(window) {
+            return None;
+        }
+        let position = Vec2::new(self.position.x, self.position.y);
+        Some(window_to_ui(bevy_window, bounds, position))
+    }
+
+    /// Draws the cursor at `position` in UI space.
+    pub(crate) fn draw<Theme>(&self, renderer: &mut iced_wgpu::Renderer<Theme>, position: Point) {
+        use iced_native::Renderer as _;
+
+        let radius = self.size / 2.0;
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds: Rectangle::new(
+                    Point::new(position.x - radius, position.y - radius),
+                    Size::new(self.size, self.size),
+                ),
+                border_radius: radius.into(),
+                border_width: 1.0,
+                border_color: Color::BLACK,
+            },
+            self.color,
+        );
+    }
+}
+
+pub(crate) fn process_virtual_cursor(
+    mut cursor: ResMut<IcedVirtualCursor>,
+    time: Res<Time>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    windows: Query<(Entity, &Window)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    viewports: Res<ViewportResource>,
+    mut event_queue: ResMut<IcedEventQueue>,
+) {
+    if !cursor.enabled {
+        return;
+    }
+    let Some(window) = focused_window(&windows, &primary_window) else { return };
+    let (Ok((_, bevy_window)), Some(viewport)) = (windows.get(window), viewports.get(&window))
+        else { return };
+    if cursor.window != Some(window) {
+        cursor.window = Some(window);
+        cursor.position = Point::new(bevy_window.width() / 2.0, bevy_window.height() / 2.0);
+    }
+
+    let (x_axis, y_axis) = cursor.axes;
+    let mut movement = (0.0, 0.0);
+    let mut pressed = false;
+    let mut released = false;
+    for gamepad in gamepads.iter() {
+        let x = axes
+            .get(GamepadAxis::new(gamepad, x_axis))
+            .unwrap_or_default();
+        let y = axes
+            .get(GamepadAxis::new(gamepad, y_axis))
+            .unwrap_or_default();
+        if x.abs() > cursor.deadzone {
+            movement.0 += x;
+        }
+        if y.abs() > cursor.deadzone {
+            // Stick axes point up, window coordinates point down.
+            movement.1 -= y;
+        }
+
+        let button = GamepadButton::new(gamepad, cursor.button);
+        pressed |= buttons.just_pressed(button);
+        released |= buttons.just_released(button);
+    }
+
+    if movement != (0.0, 0.0) {
+        let distance = cursor.speed * time.delta_seconds();
+        cursor.position = Point::new(
+            (cursor.position.x + movement.0 * distance).clamp(0.0, bevy_window.width()),
+            (cursor.position.y + movement.1 * distance).clamp(0.0, bevy_window.height()),
+        );
+        // Like the cursor position of the UIs, the event is in their coordinates.
+        let position = cursor.ui_position(window, bevy_window, viewport.logical_size());
+        if let Some(position) = position {
+            event_queue.push(
+                window,
+                IcedEvent::Mouse(mouse::Event::CursorMoved { position }),
+            );
+        }
+    }
+    if pressed {
+        event_queue.push(
+            window,
+            IcedEvent::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)),
+        );
+    }
+    if released {
+        event_queue.push(
+            window,
+            IcedEvent::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)),
+        );
+    }
+}
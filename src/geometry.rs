@@ -0,0 +1,94 @@
+use std::any::{Any, TypeId};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+use bevy_ecs::change_detection::DetectChanges;
+use bevy_ecs::system::{NonSendMut, Res};
+use bevy_utils::HashMap;
+use iced_graphics::widget::canvas::Cache;
+
+use crate::IcedSettings;
+
+/// The geometry caches of `canvas` widgets, kept across frames so that drawings like minimaps
+/// and graphs are only tessellated again when they change or are resized.
+///
+/// Requires the `canvas` feature. Get the cache of a drawing from this non-send resource, draw
+/// through it in the `canvas::Program`, and clear it when what it shows changes:
+/// ```ignore
+/// struct Minimap {
+///     cache: Rc<canvas::Cache>,
+///     map: Map,
+/// }
+///
+/// impl canvas::Program<UiMessage> for Minimap {
+///     type State = ();
+///
+///     fn draw(&self, _: &(), _: &Theme, bounds: Rectangle, _: Cursor) -> Vec<Geometry> {
+///         vec![self.cache.draw(bounds.size(), |frame| draw_map(frame, &self.map))]
+///     }
+/// }
+///
+/// fn minimap_system(
+///     map: Res<Map>,
+///     mut caches: NonSendMut<IcedCanvasCaches>,
+///     mut ctx: IcedContext<UiMessage>,
+/// ) {
+///     let cache = caches.get::<UiMessage>("minimap");
+///     if map.is_changed() {
+///         cache.clear();
+///     }
+///     ctx.display(canvas(Minimap { cache, map: map.clone() }));
+/// }
+/// ```
+///
+/// Every cache is cleared when the [`IcedSettings`] change, since drawings usually depend on
+/// the theme.
+#[derive(Default)]
+pub struct IcedCanvasCaches {
+    caches: HashMap<(TypeId, u64), Rc<Cache>>,
+}
+
+impl IcedCanvasCaches {
+    /// Returns the cache of the drawing with the given key in UIs with the message type `M`,
+    /// creating an empty one the first time.
+    pub fn get<M: Any>(&mut self, key: impl Hash) -> Rc<Cache> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.caches
+            .entry((TypeId::of::<M>(), hasher.finish()))
+            .or_insert_with(|| Rc::new(Cache::new()))
+            .clone()
+    }
+
+    /// Drop the cache of the drawing with the given key in UIs with the message type `M`.
+    pub fn remove<M: Any>(&mut self, key: impl Hash) {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.caches.remove(&(TypeId::of::<M>(), hasher.finish()));
+    }
+
+    /// Clear the geometry of every drawing in UIs with the message type `M`, so they're drawn
+    /// again.
+    pub fn clear<M: Any>(&mut self) {
+        let message = TypeId::of::<M>();
+        self.caches
+            .iter()
+            .filter(|((message_type, _), _)| *message_type == message)
+            .for_each(|(_, cache)| cache.clear());
+    }
+
+    /// Clear the geometry of every drawing.
+    pub fn clear_all(&mut self) {
+        self.caches.values().for_each(|cache| cache.clear());
+    }
+}
+
+pub(crate) fn clear_canvas_caches<Theme: Send + Sync + 'static>(
+    settings: Res<IcedSettings<Theme>>,
+    mut caches: NonSendMut<IcedCanvasCaches>,
+) {
+    if settings.is_changed() {
+        caches.clear_all();
+    }
+}
@@ -0,0 +1,177 @@
+use bevy_ecs::prelude::{Entity, EventReader, Local, Query, Res, ResMut, Resource, With};
+use bevy_input::touch::{TouchInput, TouchPhase};
+use bevy_utils::{HashMap, HashSet};
+use bevy_window::{PrimaryWindow, Window};
+use iced_native::touch::{self, Finger};
+use iced_native::{mouse, Event as IcedEvent, Point};
+
+use crate::render::ViewportResource;
+use crate::systems::{focused_window, window_to_ui, IcedEventQueue};
+
+/// Configures how two-finger touch gestures are translated into Iced scroll events.
+///
+/// Requires the `touch` feature. Spreading two fingers apart scrolls up by lines, like turning
+/// a mouse wheel to zoom in a canvas, and moving both fingers in the same direction scrolls by
+/// pixels. A gesture is a pinch or a pan depending on which movement first passes the
+/// threshold, and stays so until a finger is lifted. Set a sensitivity to 0 to disable that
+/// gesture.
+///
+/// While a gesture is made, the touch events of its fingers aren't forwarded to the UIs, which
+/// receive a `FingerLost` event for them instead when the gesture is recognized.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct IcedTouchGestures {
+    /// The lines scrolled for each logical pixel that the fingers move apart.
+    pub pinch_sensitivity: f32,
+    /// The pixels scrolled for each logical pixel that both fingers move.
+    pub pan_sensitivity: f32,
+    /// How far, in logical pixels, the distance between the fingers has to change for a pinch,
+    /// or the point between them has to move for a pan, before the gesture is recognized.
+    pub threshold: f32,
+}
+
+impl Default for IcedTouchGestures {
+    fn default() -> Self {
+        Self {
+            pinch_sensitivity: 0.02,
+            pan_sensitivity: 1.0,
+            threshold: 16.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum GestureKind {
+    Pinch,
+    Pan,
+}
+
+// The fingers touching the focused window, and the gesture that two of them make.
+#[derive(Default)]
+pub(crate) struct TouchGesture {
+    fingers: HashMap<u64, Point>,
+    // Where the fingers were when the second one touched the screen.
+    start: Option<(Point, f32)>,
+    kind: Option<GestureKind>,
+    // The fingers whose touch events are held back from the UIs until they're lifted.
+    captured: HashSet<u64>,
+}
+
+/// Sends scroll events for the pinch and pan gestures of the fingers on the focused window.
+pub(crate) fn process_touch_gestures(
+    config: Res<IcedTouchGestures>,
+    mut touch_input: EventReader<TouchInput>,
+    windows: Query<(Entity, &Window)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    viewports: Res<ViewportResource>,
+    mut gesture: Local<TouchGesture>,
+    mut event_queue: ResMut<IcedEventQueue>,
+) {
+    let Some(window) = focused_window(&windows, &primary_window) else { return };
+    let (Ok((_, bevy_window)), Some(viewport)) = (windows.get(window), viewports.get(&window))
+        else { return };
+
+    let gesture = &mut *gesture;
+    let mut lost = Vec::new();
+    for ev in touch_input.iter() {
+        let before = two_fingers(&gesture.fingers);
+        let position = window_to_ui(bevy_window, viewport.logical_size(), ev.position);
+        match ev.phase {
+            TouchPhase::Started | TouchPhase::Moved => gesture.fingers.insert(ev.id, position),
+            TouchPhase::Ended | TouchPhase::Cancelled => gesture.fingers.remove(&ev.id),
+        };
+        let after = two_fingers(&gesture.fingers);
+        if after.is_none() {
+            // Lifting a finger or adding a third one ends the gesture.
+            gesture.start = None;
+            gesture.kind = None;
+        } else if gesture.start.is_none() {
+            gesture.start = after;
+        }
+        if ev.phase != TouchPhase::Moved {
+            continue;
+        }
+        let (Some((center, spread)), Some((new_center, new_spread)), Some((start, start_spread))) =
+            (before, after, gesture.start) else { continue };
+
+        if gesture.kind.is_none() {
+            // Spreading the fingers also moves the point between them a little, so a pinch
+            // takes precedence.
+            gesture.kind = if (new_spread - start_spread).abs() >= config.threshold {
+                Some(GestureKind::Pinch)
+            } else if new_center.distance(start) >= config.threshold {
+                Some(GestureKind::Pan)
+            } else {
+                None
+            };
+            if gesture.kind.is_some() {
+                for (id, position) in &gesture.fingers {
+                    if gesture.captured.insert(*id) {
+                        lost.push(touch::Event::FingerLost {
+                            id: Finger(*id),
+                            position: *position,
+                        });
+                    }
+                }
+            }
+        }
+
+        match gesture.kind {
+            Some(GestureKind::Pinch) => {
+                let lines = (new_spread - spread) * config.pinch_sensitivity;
+                if lines != 0.0 {
+                    event_queue.push(
+                        window,
+                        IcedEvent::Mouse(mouse::Event::WheelScrolled {
+                            delta: mouse::ScrollDelta::Lines { x: 0.0, y: lines },
+                        }),
+                    );
+                }
+            }
+            Some(GestureKind::Pan) => {
+                let pan = new_center - center;
+                let (x, y) = (
+                    pan.x * config.pan_sensitivity,
+                    pan.y * config.pan_sensitivity,
+                );
+                if x != 0.0 || y != 0.0 {
+                    event_queue.push(
+                        window,
+                        IcedEvent::Mouse(mouse::Event::WheelScrolled {
+                            delta: mouse::ScrollDelta::Pixels { x, y },
+                        }),
+                    );
+                }
+            }
+            None => {}
+        }
+    }
+
+    if !gesture.captured.is_empty() {
+        let captured = &gesture.captured;
+        event_queue.retain_window(window, |event| match event {
+            IcedEvent::Touch(
+                touch::Event::FingerPressed { id, .. }
+                | touch::Event::FingerMoved { id, .. }
+                | touch::Event::FingerLifted { id, .. }
+                | touch::Event::FingerLost { id, .. },
+            ) => !captured.contains(&id.0),
+            _ => true,
+        });
+        let fingers = &gesture.fingers;
+        gesture.captured.retain(|id| fingers.contains_key(id));
+    }
+    for event in lost {
+        event_queue.push(window, IcedEvent::Touch(event));
+    }
+}
+
+// The point between the fingers and their distance, if exactly two fingers touch the screen.
+fn two_fingers(fingers: &HashMap<u64, Point>) -> Option<(Point, f32)> {
+    if fingers.len() != 2 {
+        return None;
+    }
+    let mut points = fingers.values();
+    let (a, b) = (points.next()?, points.next()?);
+    let center = Point::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0);
+    Some((center, a.distance(*b)))
+}
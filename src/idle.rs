@@ -0,0 +1,115 @@
+use bevy_ecs::change_detection::DetectChanges;
+use bevy_ecs::prelude::{Changed, Entity, Query, RemovedComponents};
+use bevy_ecs::system::{Res, ResMut, Resource};
+use bevy_time::Time;
+use bevy_utils::HashMap;
+use iced_native::time::Instant;
+use iced_native::window::{self, RedrawRequest};
+use iced_native::{Event as IcedEvent, Size};
+
+use crate::render::ViewportResource;
+use crate::systems::IcedEventQueue;
+use crate::IcedSettings;
+
+/// Stops running the UI systems while nothing happens, to save power in tool-style
+/// applications that sit idle.
+///
+/// Once enabled with [`IcedPlugin::with_idle_frames`](crate::IcedPlugin::with_idle_frames),
+/// [`IcedSet::Display`](crate::IcedSet::Display) and [`IcedSet::Draw`](crate::IcedSet::Draw)
+/// are skipped after that many frames without input, resized windows, changed
+/// [`IcedSettings`] or widget animations, and the last frame of every UI is drawn again until
+/// one of those happens. Call [`IcedIdle::wake`] when the state shown by the UIs changes
+/// without any input, e.g. when a file finished loading.
+#[derive(Resource, Default)]
+pub struct IcedIdle {
+    idle_after: Option<u32>,
+    // The number of frames in a row without any activity.
+    quiet_frames: u32,
+    woken: bool,
+    // When the earliest widget animation wants to be drawn again.
+    redraw_at: Option<Instant>,
+    viewports: HashMap<Entity, (Size<u32>, f64)>,
+}
+
+impl IcedIdle {
+    pub(crate) fn new(idle_after: Option<u32>) -> Self {
+        Self {
+            idle_after,
+            ..Default::default()
+        }
+    }
+
+    /// Whether the UI systems are currently skipped.
+    pub fn is_idle(&self) -> bool {
+        self.idle_after
+            .map_or(false, |frames| self.quiet_frames > frames)
+    }
+
+    /// Run the UI systems again from this frame on, until the app is idle for long enough.
+    pub fn wake(&mut self) {
+        self.woken = true;
+        self.quiet_frames = 0;
+    }
+
+    /// Skip the UI systems after the given number of frames without activity, or never if
+    /// `None`.
+    pub fn set_idle_after(&mut self, frames: Option<u32>) {
+        self.idle_after = frames;
+        self.quiet_frames = 0;
+    }
+
+    // Wakes the app when a widget wants to be drawn again, e.g. to blink a text cursor.
+    pub(crate) fn request_redraw(&mut self, at: Instant) {
+        self.redraw_at = Some(self.redraw_at.map_or(at, |redraw_at| redraw_at.min(at)));
+    }
+}
+
+/// The time a widget wants to be drawn again.
+pub(crate) fn redraw_time(request: RedrawRequest) -> Instant {
+    match request {
+        RedrawRequest::NextFrame => Instant::now(),
+        RedrawRequest::At(at) => at,
+    }
+}
+
+/// Whether an event only asks the UI to redraw, which doesn't count as activity.
+pub(crate) fn is_redraw(event: &IcedEvent) -> bool {
+    matches!(event, IcedEvent::Window(window::Event::RedrawRequested(_)))
+}
+
+/// Counts the frames without activity, waking the UIs as soon as something happens.
+pub(crate) fn update_idle<Theme: Send + Sync + 'static>(
+    mut idle: ResMut<IcedIdle>,
+    events: Res<IcedEventQueue>,
+    viewports: Res<ViewportResource>,
+    settings: Res<IcedSettings<Theme>>,
+    window_settings: Query<(), Changed<IcedSettings<Theme>>>,
+    mut removed_settings: RemovedComponents<IcedSettings<Theme>>,
+    time: Res<Time>,
+) {
+    let now = time.last_update().unwrap_or_else(Instant::now);
+    let viewports: HashMap<_, _> = viewports
+        .iter()
+        .map(|(entity, viewport)| (*entity, (viewport.physical_size(), viewport.scale_factor())))
+        .collect();
+    // Read every frame, so removals aren't seen again after waking for another reason.
+    let settings_removed = removed_settings.iter().count() > 0;
+    let redraw_due = idle.redraw_at.map_or(false, |at| at <= now);
+    let active = std::mem::take(&mut idle.woken)
+        || redraw_due
+        || settings.is_changed()
+        || !window_settings.is_empty()
+        || settings_removed
+        || viewports != idle.viewports
+        || events.iter().any(|event| !is_redraw(event));
+
+    if redraw_due {
+        idle.redraw_at = None;
+    }
+    idle.viewports = viewports;
+    idle.quiet_frames = if active {
+        0
+    } else {
+        idle.quiet_frames.saturating_add(1)
+    };
+}
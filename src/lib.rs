@@ -26,200 +26,1691 @@
 //!
 //! ## Feature flags
 //!
-//! - `touch`: Enables touch input. Is not exclude input from the mouse.
+//! The widget features (`image`, `svg`, `canvas` and `qr_code`) enable the matching features of
+//! `iced_wgpu` and `iced_graphics`, so apps only compile the widgets they use.
+//!
+//! - `touch`: Enables touch input. Is not exclude input from the mouse. Two-finger gestures
+//!   scroll, as configured by [`IcedTouchGestures`], and pen pressure is available in
+//!   [`IcedStylus`].
+//! - `clipboard`: Enables cut, copy and paste in text inputs using the system clipboard.
+//!   On the web, the browser clipboard is used and pasted text arrives on a following frame;
+//!   this requires building with `RUSTFLAGS=--cfg=web_sys_unstable_apis`.
+//! - `webgl`: Renders with WebGL2 in `wasm32` builds. Enable Bevy's `webgl` feature as well.
+//! - `image`: Enables Iced `image` widgets, and showing Bevy images in them with [`IcedImages`].
+//! - `svg`: Enables Iced `svg` widgets, and loading SVG files as [`IcedSvg`] assets.
+//! - `canvas`: Re-exports Iced's [`canvas`] widget, with [`IcedCanvasCaches`] to keep the
+//!   tessellated geometry of drawings across frames.
+//! - `qr_code`: Re-exports Iced's [`qr_code`] widget.
+//! - `debug`: Records the metrics shown by [`IcedSettings::show_debug_overlay`].
+//! - `serde`: Makes [`testing::PrimitiveSnapshot`] serializable, to store golden files.
+//! - `picking`: Provides [`IcedPickingPlugin`], which stops `bevy_mod_picking` from picking
+//!   entities behind the UIs.
+//! - `system_theme`: Provides [`IcedSystemThemePlugin`], which follows the light or dark theme
+//!   of the OS.
+//! - `lazy`: Re-exports [`iced_lazy`], with the `lazy`, `responsive` and `component` widgets.
+//! - `gamepad`: Translates gamepad input into keyboard navigation, configured through
+//!   [`IcedGamepadNavigation`], and provides a stick-driven [`IcedVirtualCursor`].
+//!
+//! ## Lazy widgets
+//!
+//! The state of `iced_lazy` widgets lives in the widget tree, which [`IcedCache`] keeps for
+//! every display call of a message type in a window, like the state of other widgets. So
+//! `lazy` only rebuilds its content when its dependency changes, `component` keeps its state,
+//! and `responsive` lays out its content again when the UI is resized. With `responsive`, a
+//! list of thousands of rows can build only the rows in view, see the `virtual_list` example.
+//! The n-th display call of a frame gets the state of the n-th call of the last one, so UIs
+//! with lazy widgets that aren't displayed every frame should be [`IcedUi`] entities.
+//!
+//! ## File drops
+//!
+//! Files dragged onto a window are sent to its UIs as Iced `FileHovered`, `FileDropped` and
+//! `FilesHoveredLeft` window events. Neither Bevy nor Iced gives these events a position, so
+//! the drop position isn't available: widgets only know the last cursor position of the
+//! window, which most platforms don't update while files are dragged over it.
+//!
+//! ## Diagnostics
+//!
+//! Add [`IcedDiagnosticsPlugin`] to measure the time spent on UIs and the number of primitives
+//! they draw with Bevy's diagnostics.
+//! Input processing, display calls, extraction and rendering are also wrapped in `iced_*`
+//! tracing spans, to profile them with Bevy's `trace_tracy` or `trace_chrome` features.
+//!
+//! ## Testing
+//!
+//! The [`testing`] module drives UIs with synthetic input in headless tests.
+//! To reproduce a bug, record the input of a session with [`IcedInputRecorder`] and feed it
+//! back with [`IcedInputReplay`].
+//! [`IcedContext::export_svg`] writes the UIs of a frame as an SVG document, to review or diff
+//! their layout without a GPU.
 
 #![deny(unsafe_code)]
 #![deny(missing_docs)]
 
 use std::any::{Any, TypeId};
+use std::borrow::Cow;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 
-use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 
+use crate::render::ExtractedClearColors;
+use crate::render::ExtractedFrames;
+use crate::render::ExtractedOverlay;
+use crate::render::ExtractedRenderTargets;
+use crate::render::GlyphWarmup;
+use crate::render::IcedFrames;
 use crate::render::IcedNode;
+use crate::render::TrimCaches;
 use crate::render::ViewportResource;
 
-use bevy_app::{App, IntoSystemAppConfig, Plugin};
-use bevy_derive::{Deref, DerefMut};
+use bevy_app::{App, CoreSet, IntoSystemAppConfig, Plugin};
+use bevy_asset::{AddAsset, Handle};
+use bevy_ecs::change_detection::DetectChanges;
 use bevy_ecs::event::Event;
-use bevy_ecs::prelude::{EventWriter, Query, With};
+use bevy_ecs::prelude::{Component, Entity, EventWriter, Query, Ref, ReflectResource, With};
+use bevy_ecs::schedule::{IntoSystemConfig, IntoSystemSetConfigs, SystemSet};
 use bevy_ecs::system::{NonSendMut, Res, ResMut, Resource, SystemParam};
+use bevy_ecs::world::FromWorld;
 #[cfg(feature = "touch")]
 use bevy_input::touch::Touches;
-use bevy_math::Vec2;
+use bevy_math::{Vec2, Vec3};
+use bevy_reflect::Reflect;
+use bevy_render::camera::{Camera, NormalizedRenderTarget};
+use bevy_render::color::Color;
 use bevy_render::render_graph::RenderGraph;
 use bevy_render::renderer::RenderDevice;
-use bevy_render::{ExtractSchedule, RenderApp};
-use bevy_utils::HashMap;
-use bevy_window::{PrimaryWindow, Window};
+use bevy_render::texture::Image;
+use bevy_render::{ExtractSchedule, RenderApp, RenderSet};
+use bevy_time::Time;
+use bevy_transform::components::GlobalTransform;
+use bevy_utils::synccell::SyncCell;
+use bevy_utils::tracing::info_span;
+use bevy_utils::{HashMap, Instant};
+use bevy_window::{CursorGrabMode, CursorIcon, PrimaryWindow, Window};
 use iced::{user_interface, Element, UserInterface};
 pub use iced_native as iced;
-use iced_native::{Debug, Point, Size};
 use iced_native::event::Status;
+use iced_native::text::Renderer as _;
+use iced_native::widget::{self, operation::Focusable, Operation};
+use iced_native::window::RedrawRequest;
+use iced_native::{command::Action, Command};
+use iced_native::{Background, Debug, Font, Point, Rectangle, Vector};
+#[cfg(feature = "canvas")]
+pub use iced_graphics::widget::canvas;
+#[cfg(feature = "qr_code")]
+pub use iced_graphics::widget::qr_code;
+#[cfg(feature = "lazy")]
+pub use iced_lazy;
 pub use iced_wgpu;
-use iced_wgpu::{wgpu, Settings, Viewport, Primitive};
+use iced_wgpu::{wgpu, Primitive, Settings};
 
-mod conversions;
+mod assets;
+mod batch;
+mod capture;
+mod clipboard;
+mod close;
+mod command;
+mod conditions;
+pub mod conversions;
+mod deps;
+mod diagnostics;
+#[cfg(feature = "gamepad")]
+mod gamepad;
+#[cfg(feature = "canvas")]
+mod geometry;
+#[cfg(feature = "touch")]
+mod gestures;
+mod idle;
+#[cfg(feature = "picking")]
+mod picking;
+mod program;
+mod record;
+mod reflect;
 mod render;
+#[cfg(feature = "touch")]
+mod stylus;
+#[cfg(feature = "system_theme")]
+mod system_theme;
 mod systems;
+pub mod testing;
+pub mod time;
+mod tooltip;
+mod ui;
 
-use systems::IcedEventQueue;
+#[cfg(feature = "svg")]
+pub use assets::IcedSvg;
+pub use assets::{IcedFont, IcedFonts};
+#[cfg(feature = "image")]
+pub use assets::{IcedImageCache, IcedImages};
+pub use capture::IcedCapture;
+use capture::IcedCaptureRequests;
+use clipboard::BoxedClipboard;
+pub use close::IcedCloseRequests;
+use command::CommandQueue;
+pub use conditions::{keyboard_captured, pointer_over_ui};
+pub use deps::IcedDependencies;
+use diagnostics::IcedTimings;
+pub use diagnostics::{IcedDiagnosticsPlugin, IcedFrameStats};
+#[cfg(feature = "gamepad")]
+pub use gamepad::{IcedGamepadNavigation, IcedVirtualCursor, NavigationKey};
+#[cfg(feature = "canvas")]
+pub use geometry::IcedCanvasCaches;
+#[cfg(feature = "touch")]
+pub use gestures::IcedTouchGestures;
+use iced_native::clipboard::Clipboard;
+pub use idle::IcedIdle;
+#[cfg(feature = "picking")]
+pub use picking::IcedPickingPlugin;
+pub use program::IcedProgram;
+pub use record::{IcedInputRecorder, IcedInputReplay};
+pub use reflect::{IcedReflectedStyle, IcedThemeKind};
+pub use render::ICED_PASS;
+#[cfg(feature = "touch")]
+pub use stylus::IcedStylus;
+#[cfg(feature = "system_theme")]
+pub use system_theme::{IcedSystemTheme, IcedSystemThemeChanged, IcedSystemThemePlugin};
+use systems::{
+    IcedCapturedInput, IcedEventQueue, IcedInteractions, IcedKeyboardState, IcedSyntheticInput,
+};
+pub use tooltip::IcedTooltipSettings;
+pub use ui::{display_ui_components, IcedUi};
 
 /// The main feature of `bevy_iced`.
 /// Add this to your [`App`] by calling `app.add_plugin(bevy_iced::IcedPlugin)`.
-pub struct IcedPlugin {
+///
+/// The plugin is generic over the theme used to draw the UI, which defaults to
+/// [`iced_wgpu::Theme`]. Use [`IcedPlugin::with_theme`] to draw with a custom theme type;
+/// [`IcedContext`] and [`IcedSettings`] then need the same type parameter.
+pub struct IcedPlugin<Theme = iced_wgpu::Theme> {
     settings: Option<Settings>,
+    texture_format: Option<wgpu::TextureFormat>,
+    clipboard: Mutex<Option<BoxedClipboard>>,
+    fonts: Vec<&'static [u8]>,
+    layer_order: IcedLayerOrder,
+    cache_eviction: Option<u64>,
+    idle_frames: Option<u32>,
+    pass_placement: IcedPassPlacement,
+    pass_edges: Vec<PassEdge>,
+    theme: Theme,
+}
+
+/// Where the Iced render pass, labelled [`ICED_PASS`], is added to the render graph, see
+/// [`IcedPlugin::with_pass_placement`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IcedPassPlacement {
+    /// In the main graph, after the cameras are rendered.
+    #[default]
+    AfterCameras,
+    /// In the main graph, ordered only by [`IcedPlugin::render_before`] and
+    /// [`IcedPlugin::render_after`].
+    Main,
+    /// In the given sub-graph of the main graph, ordered only by [`IcedPlugin::render_before`]
+    /// and [`IcedPlugin::render_after`]. The pass renders every window and render target each
+    /// time it runs, so the sub-graph should run once per frame, not once per camera.
+    SubGraph(&'static str),
+}
+
+// An edge between the Iced render pass and another node of the main render graph.
+#[derive(Clone, Copy)]
+enum PassEdge {
+    Before(&'static str),
+    After(&'static str),
 }
 
 impl IcedPlugin {
     /// Creates an instance of the plugin with default `iced` settings.
     pub fn default() -> IcedPlugin {
-        Self { settings: None }
+        Self {
+            settings: None,
+            texture_format: None,
+            clipboard: Mutex::new(None),
+            fonts: Vec::new(),
+            layer_order: IcedLayerOrder::default(),
+            cache_eviction: None,
+            idle_frames: None,
+            pass_placement: IcedPassPlacement::default(),
+            pass_edges: Vec::new(),
+            theme: iced_wgpu::Theme::Dark,
+        }
     }
 
     /// Creates an instance of the plugin with custom `iced` settings.
     pub fn with_settings(settings: Settings) -> IcedPlugin {
-        Self { settings: Some(settings) }
+        Self {
+            settings: Some(settings),
+            ..Self::default()
+        }
+    }
+}
+
+impl<Theme> IcedPlugin<Theme> {
+    /// Draw the UI with a custom theme type, starting out with the given theme.
+    ///
+    /// Use `IcedContext<Message, T>` in your systems and `IcedSettings<T>` to change the
+    /// theme at runtime.
+    pub fn with_theme<T>(self, theme: T) -> IcedPlugin<T> {
+        IcedPlugin {
+            settings: self.settings,
+            texture_format: self.texture_format,
+            clipboard: self.clipboard,
+            fonts: self.fonts,
+            layer_order: self.layer_order,
+            cache_eviction: self.cache_eviction,
+            idle_frames: self.idle_frames,
+            pass_placement: self.pass_placement,
+            pass_edges: self.pass_edges,
+            theme,
+        }
+    }
+
+    /// Render Iced elements in windows for the given texture format, instead of the format of
+    /// each window surface. Render target images are always rendered in their own format.
+    pub fn with_texture_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.texture_format = Some(format);
+        self
+    }
+
+    /// Smooth the edges of meshes drawn by the UI, like canvas shapes, with multisampling.
+    ///
+    /// This is independent of Bevy's `Msaa` resource: the UI is drawn after the cameras, on
+    /// top of the resolved image, with its own multisampled buffers. Text and quads are always
+    /// antialiased.
+    pub fn with_antialiasing(mut self, antialiasing: Option<iced_wgpu::Antialiasing>) -> Self {
+        let settings = self.settings.get_or_insert_with(Default::default);
+        settings.antialiasing = antialiasing;
+        self
+    }
+
+    /// Load fonts to use in the UI. They are available from the [`IcedFonts`] resource, in the
+    /// same order.
+    pub fn with_fonts(mut self, fonts: Vec<Cow<'static, [u8]>>) -> Self {
+        self.fonts.extend(fonts.into_iter().map(leak_font));
+        self
+    }
+
+    /// Use the given font for text that doesn't specify one, instead of the built-in font.
+    pub fn with_default_font(mut self, font: Cow<'static, [u8]>) -> Self {
+        let settings = self.settings.get_or_insert_with(Default::default);
+        settings.default_font = Some(leak_font(font));
+        self
+    }
+
+    /// Draw the UIs with the message type `M` at the given z-order, see [`IcedLayerOrder`].
+    pub fn register_layer<M: Event>(mut self, z: i32) -> Self {
+        self.layer_order.set::<M>(z);
+        self
+    }
+
+    /// Drop the interface state of UIs that weren't displayed for the given number of frames.
+    ///
+    /// By default, the state is kept until it is cleared through [`IcedCache`].
+    pub fn with_cache_eviction(mut self, frames: u64) -> Self {
+        self.cache_eviction = Some(frames);
+        self
+    }
+
+    /// Stop running the UI systems after the given number of frames without activity, and
+    /// draw the last frame of every UI until something happens, see [`IcedIdle`].
+    pub fn with_idle_frames(mut self, frames: u32) -> Self {
+        self.idle_frames = Some(frames);
+        self
+    }
+
+    /// Add the Iced render pass to another place of the render graph than after the cameras,
+    /// for apps with custom render graphs.
+    pub fn with_pass_placement(mut self, placement: IcedPassPlacement) -> Self {
+        self.pass_placement = placement;
+        self
+    }
+
+    /// Render the UIs before the given node of the render graph, so that anything it draws
+    /// covers them, e.g. `render_before("egui_pass")` to draw `bevy_egui` windows on top.
+    ///
+    /// The UIs are rendered after the cameras, and `bevy_ui` is drawn by the cameras, so the
+    /// UIs always cover `bevy_ui` nodes. Nodes that don't exist once every plugin is built are
+    /// ignored.
+    pub fn render_before(mut self, node: &'static str) -> Self {
+        self.pass_edges.push(PassEdge::Before(node));
+        self
+    }
+
+    /// Render the UIs after the given node of the render graph, so that they cover
+    /// anything it draws, e.g. `render_after("egui_pass")` to draw on top of `bevy_egui`.
+    ///
+    /// Nodes that don't exist once every plugin is built are ignored.
+    pub fn render_after(mut self, node: &'static str) -> Self {
+        self.pass_edges.push(PassEdge::After(node));
+        self
+    }
+
+    /// Use a custom [`Clipboard`] for text inputs instead of the default one.
+    ///
+    /// By default, the system clipboard is used when the `clipboard` feature is enabled,
+    /// and clipboard operations are ignored otherwise.
+    pub fn with_clipboard(self, clipboard: impl Clipboard + Send + 'static) -> Self {
+        *self.clipboard.lock().unwrap() = Some(Box::new(clipboard));
+        self
     }
 }
 
-impl Plugin for IcedPlugin {
+impl<Theme: Clone + Send + Sync + 'static> Plugin for IcedPlugin<Theme> {
     fn build(&self, app: &mut App) {
-        let default_viewport = Viewport::with_physical_size(Size::new(1600, 900), 1.0);
-        let default_viewport = ViewportResource(default_viewport);
-        let settings = self.settings.unwrap_or(Default::default());
-        let iced_resource: IcedResource = IcedProps::new(app, settings).into();
-
-        app.add_system(systems::process_input)
-            .add_system(render::update_viewport)
-            .insert_resource(DidDraw::default())
-            .insert_resource(iced_resource.clone())
-            .insert_resource(IcedSettings::default())
-            .insert_non_send_resource(IcedCache::default())
+        let settings = platform_settings(self.settings.unwrap_or(Default::default()));
+        let clipboard = self.clipboard.lock().unwrap().take();
+        let clipboard = clipboard.unwrap_or_else(|| clipboard::default_clipboard(app));
+        // Without a render device, e.g. on a dedicated server, the UIs are never displayed.
+        let renderer = app
+            .get_sub_app(RenderApp)
+            .ok()
+            .and_then(|render_app| render_app.world.get_resource::<RenderDevice>())
+            .map(|device| IcedRenderer::<Theme>::new(device, settings));
+        if let Some(renderer) = renderer {
+            app.insert_non_send_resource(renderer);
+        }
+
+        app.configure_sets(
+            (IcedSet::ProcessInput, IcedSet::UpdateViewport).before(IcedSet::Display),
+        )
+        .configure_sets((IcedSet::ConsumeInput, IcedSet::Draw).after(IcedSet::Display))
+        .configure_set(IcedSet::Display.run_if(not_idle))
+        .configure_set(IcedSet::Draw.run_if(not_idle));
+        app.add_system(systems::process_input.in_set(IcedSet::ProcessInput))
+            .add_system(render::update_viewport::<Theme>.in_set(IcedSet::UpdateViewport))
+            .add_system(render::update_renderer::<Theme>.in_set(IcedSet::UpdateViewport))
+            .add_system(
+                systems::inject_synthetic_input
+                    .after(systems::process_input)
+                    .in_set(IcedSet::ProcessInput),
+            )
+            .add_system(
+                systems::repeat_keys
+                    .after(systems::process_input)
+                    .before(systems::inject_synthetic_input)
+                    .in_set(IcedSet::ProcessInput),
+            )
+            .add_system(
+                systems::snap_double_clicks
+                    .after(systems::inject_synthetic_input)
+                    .before(record::replay_input)
+                    .in_set(IcedSet::ProcessInput),
+            )
+            .add_system(
+                record::replay_input
+                    .after(systems::inject_synthetic_input)
+                    .in_set(IcedSet::ProcessInput),
+            )
+            .add_system(
+                systems::request_redraw
+                    .after(record::replay_input)
+                    .in_set(IcedSet::ProcessInput),
+            )
+            .add_system(
+                systems::send_resize_events
+                    .after(systems::process_input)
+                    .after(IcedSet::UpdateViewport)
+                    .in_set(IcedSet::ProcessInput),
+            )
+            .add_system(
+                close::receive_close_requests
+                    .after(systems::process_input)
+                    .in_set(IcedSet::ProcessInput),
+            )
+            .add_system(close::close_confirmed_windows.in_base_set(CoreSet::PostUpdate))
+            .add_system(
+                record::record_input
+                    .after(IcedSet::ProcessInput)
+                    .before(IcedSet::Display),
+            )
+            .add_system(systems::consume_captured_input.in_set(IcedSet::ConsumeInput))
+            .add_system(systems::update_cursor_icons.after(IcedSet::Draw))
+            .add_system(
+                systems::apply_cursor_mode
+                    .before(systems::hit_test_previous_frame)
+                    .in_base_set(CoreSet::PreUpdate),
+            )
+            .add_system(systems::swap_occlusion_rects.in_base_set(CoreSet::PreUpdate))
+            .add_system(
+                systems::hit_test_previous_frame
+                    .before(systems::new_frame)
+                    .in_base_set(CoreSet::PreUpdate)
+                    .run_if(not_idle),
+            )
+            .add_system(
+                systems::route_camera_input
+                    .after(record::record_input)
+                    .before(systems::filter_events),
+            )
+            .add_system(
+                systems::filter_events
+                    .after(record::record_input)
+                    .before(idle::update_idle::<Theme>)
+                    .before(IcedSet::Display),
+            )
+            .add_system(
+                idle::update_idle::<Theme>
+                    .after(IcedSet::ProcessInput)
+                    .after(IcedSet::UpdateViewport)
+                    .before(IcedSet::Display),
+            )
+            .add_system(systems::new_frame.in_base_set(CoreSet::PreUpdate))
+            .add_system(assets::register_font_assets.before(IcedSet::Display))
+            .add_system(systems::toggle_debug_overlay::<Theme>.before(IcedSet::Display))
+            .insert_resource(IcedClipboard(SyncCell::new(clipboard)))
+            .insert_resource(IcedDebug {
+                debug: Debug::new(),
+                enabled: false,
+            })
+            .init_resource::<IcedFrames>()
+            .init_resource::<IcedTimings>()
+            .insert_resource(IcedIdle::new(self.idle_frames))
+            .insert_resource(IcedSettings {
+                renderer: settings,
+                ..IcedSettings::new(self.theme.clone())
+            })
+            .insert_non_send_resource(IcedCache {
+                evict_after: self.cache_eviction,
+                ..Default::default()
+            })
             .insert_resource(IcedEventQueue::default())
             .init_resource::<IcedDisplayResult>()
-            .insert_resource(default_viewport.clone());
+            .init_resource::<IcedCapturedInput>()
+            .init_resource::<IcedSyntheticInput>()
+            .init_resource::<IcedKeyboardState>()
+            .init_resource::<InputConsumptionPolicy>()
+            .init_resource::<IcedInputConfig>()
+            .init_resource::<IcedEventFilters>()
+            .init_resource::<IcedDoubleClick>()
+            .init_resource::<IcedKeyRepeat>()
+            .init_resource::<IcedKeyMapping>()
+            .init_resource::<IcedScrollConfig>()
+            .init_resource::<IcedCloseRequests>()
+            .init_resource::<IcedSafeArea>()
+            .insert_resource(self.layer_order.clone())
+            .init_resource::<IcedModalLayer>()
+            .init_resource::<IcedTooltipSettings>()
+            .init_resource::<IcedInteractions>()
+            .init_resource::<IcedCursorPolicy>()
+            .init_resource::<IcedCursorMode>()
+            .init_resource::<IcedCursorIcons>()
+            .init_resource::<IcedImePreedit>()
+            .insert_resource(IcedFonts::new(&self.fonts))
+            .add_asset::<IcedFont>()
+            .init_asset_loader::<assets::IcedFontLoader>()
+            .init_resource::<IcedOcclusionRects>()
+            .add_event::<IcedLifecycle>()
+            .init_resource::<ViewportResource>()
+            .init_resource::<TrimCaches>()
+            .init_resource::<GlyphWarmup>()
+            .init_resource::<IcedCaptureRequests>()
+            .add_system(capture::start_captures.in_base_set(CoreSet::PostUpdate))
+            .add_system(capture::end_captures.in_base_set(CoreSet::PreUpdate))
+            .add_system(render::draw_glyph_warmup.in_set(IcedSet::Draw))
+            .register_type::<IcedSettings<Theme>>();
+        // Only the built-in themes can be reflected, and only default settings can be created.
+        if TypeId::of::<Theme>() == TypeId::of::<iced_wgpu::Theme>() {
+            app.register_type_data::<IcedSettings, ReflectResource>()
+                .register_type::<IcedThemeKind>()
+                .register_type::<IcedReflectedStyle>()
+                .init_resource::<IcedReflectedStyle>()
+                .add_system(reflect::sync_reflected_style.before(IcedSet::Display));
+        }
+
+        if let Ok(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ViewportResource>()
+                .init_resource::<ExtractedFrames>()
+                .init_resource::<ExtractedOverlay>()
+                .init_resource::<ExtractedRenderTargets>()
+                .init_resource::<ExtractedClearColors>()
+                .init_resource::<capture::ExtractedCaptures>()
+                .init_resource::<capture::PendingReadbacks>()
+                .add_system(capture::map_readbacks.in_set(RenderSet::Cleanup))
+                .init_resource::<render::Suspended>()
+                .add_system(render::extract_iced_data::<Theme>.in_schedule(ExtractSchedule));
+            let mut graph = render_app.world.resource_mut::<RenderGraph>();
+            let node = IcedNode::new(settings, self.texture_format);
+            match self.pass_placement {
+                IcedPassPlacement::AfterCameras => {
+                    graph.add_node(render::ICED_PASS, node);
+                    graph.add_node_edge(
+                        bevy_render::main_graph::node::CAMERA_DRIVER,
+                        render::ICED_PASS,
+                    );
+                }
+                IcedPassPlacement::Main => graph.add_node(render::ICED_PASS, node),
+                // The sub-graph may be added by a later plugin.
+                IcedPassPlacement::SubGraph(_) => {}
+            }
+        }
+
+        #[cfg(feature = "svg")]
+        app.add_asset::<IcedSvg>()
+            .init_asset_loader::<assets::IcedSvgLoader>();
 
-        let render_app = app.sub_app_mut(RenderApp);
-        render_app
-            .insert_resource(default_viewport)
-            .insert_resource(iced_resource)
-            .add_system(render::extract_iced_data.in_schedule(ExtractSchedule));
-        setup_pipeline(&mut render_app.world.get_resource_mut().unwrap());
+        #[cfg(feature = "image")]
+        app.init_resource::<IcedImageCache>()
+            .add_system(assets::invalidate_images.before(IcedSet::Display))
+            .add_system(assets::trim_images.before(render::update_renderer::<Theme>));
+
+        #[cfg(feature = "canvas")]
+        app.init_non_send_resource::<IcedCanvasCaches>()
+            .add_system(geometry::clear_canvas_caches::<Theme>.before(IcedSet::Display));
+
+        #[cfg(feature = "touch")]
+        app.init_resource::<IcedTouchGestures>()
+            .init_resource::<IcedStylus>()
+            .add_system(
+                gestures::process_touch_gestures
+                    .after(systems::process_input)
+                    .before(record::replay_input)
+                    .in_set(IcedSet::ProcessInput),
+            )
+            .add_system(
+                stylus::process_stylus_input
+                    .after(systems::process_input)
+                    .before(systems::inject_synthetic_input)
+                    .in_set(IcedSet::ProcessInput),
+            );
+
+        #[cfg(feature = "gamepad")]
+        app.init_resource::<IcedGamepadNavigation>()
+            .init_resource::<IcedVirtualCursor>()
+            .add_systems(
+                (
+                    gamepad::process_gamepad_input,
+                    gamepad::process_virtual_cursor,
+                )
+                    .after(systems::process_input)
+                    .before(record::replay_input)
+                    .in_set(IcedSet::ProcessInput),
+            );
+    }
+
+    fn setup(&self, app: &mut App) {
+        // Other plugins may add their nodes and sub-graphs after this one is built, so the pass
+        // is only placed in them once every plugin is.
+        let Ok(render_app) = app.get_sub_app_mut(RenderApp) else { return };
+        let mut graph = render_app.world.resource_mut::<RenderGraph>();
+        let graph = match self.pass_placement {
+            IcedPassPlacement::SubGraph(name) => {
+                let settings = platform_settings(self.settings.unwrap_or(Default::default()));
+                let graph = graph
+                    .get_sub_graph_mut(name)
+                    .unwrap_or_else(|| panic!("the render graph has no sub-graph named {name}"));
+                graph.add_node(
+                    render::ICED_PASS,
+                    IcedNode::new(settings, self.texture_format),
+                );
+                graph
+            }
+            _ => &mut *graph,
+        };
+        for edge in &self.pass_edges {
+            let _ = match *edge {
+                PassEdge::Before(node) => graph.try_add_node_edge(render::ICED_PASS, node),
+                PassEdge::After(node) => graph.try_add_node_edge(node, render::ICED_PASS),
+            };
+        }
     }
 }
 
-struct IcedProps {
-    renderer: iced_wgpu::Renderer,
-    debug: iced_native::Debug,
-    clipboard: iced_native::clipboard::Null,
+/// Extension methods to register UIs with an [`App`].
+pub trait IcedAppExt {
+    /// Display the [`IcedUi`] components with the message type `M` every frame.
+    ///
+    /// For a custom theme, add [`display_ui_components::<M, Theme>`](display_ui_components)
+    /// to [`IcedSet::Display`] instead.
+    fn add_iced_ui<M: Event>(&mut self) -> &mut Self;
+
+    /// Run an [`IcedProgram`], starting from its [`FromWorld`] state.
+    fn add_iced_program<P: IcedProgram + FromWorld>(&mut self) -> &mut Self;
 }
 
-impl IcedProps {
-    fn new(app: &App, settings: Settings) -> Self {
-        let device = app
-            .sub_app(RenderApp)
-            .world
-            .get_resource::<RenderDevice>()
-            .unwrap()
-            .wgpu_device();
+impl IcedAppExt for App {
+    fn add_iced_ui<M: Event>(&mut self) -> &mut Self {
+        self.add_system(display_ui_components::<M, iced_wgpu::Theme>.in_set(IcedSet::Display))
+    }
+
+    fn add_iced_program<P: IcedProgram + FromWorld>(&mut self) -> &mut Self {
+        let program = P::from_world(&mut self.world);
+        self.add_event::<P::Message>()
+            .insert_resource(program::ProgramState(program))
+            .add_system(program::run_program::<P>.in_set(IcedSet::Display))
+    }
+}
+
+/// System sets used by `bevy_iced`, to order your systems relative to the plugin.
+///
+/// Add your UI systems to [`IcedSet::Display`] to make sure they see this frame's input:
+/// ```ignore
+/// app.add_system(ui_system.in_set(IcedSet::Display));
+/// ```
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IcedSet {
+    /// Converts Bevy input into Iced events.
+    ProcessInput,
+    /// Updates the viewports of windows and render targets.
+    UpdateViewport,
+    /// Systems that display UIs. Runs after [`IcedSet::ProcessInput`] and
+    /// [`IcedSet::UpdateViewport`].
+    Display,
+    /// Systems that draw UIs updated in [`IcedSet::Display`] with [`IcedContext::update`].
+    /// Runs after [`IcedSet::Display`].
+    Draw,
+    /// Removes captured input from Bevy's `Input` resources, according to the
+    /// [`InputConsumptionPolicy`]. Runs after [`IcedSet::Display`].
+    ConsumeInput,
+}
+
+// The renderer needs font data for the lifetime of the app, so owned data is leaked, once for
+// each distinct font.
+fn leak_font(font: Cow<'static, [u8]>) -> &'static [u8] {
+    assets::intern_font(font)
+}
+
+// Adjusts the renderer settings to what the platform supports.
+#[cfg(target_arch = "wasm32")]
+fn platform_settings(settings: Settings) -> Settings {
+    Settings {
+        // Browsers can't spawn the threads used to lay out text in parallel.
+        text_multithreading: false,
+        ..settings
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn platform_settings(settings: Settings) -> Settings {
+    settings
+}
+
+// The clipboard used by text inputs and clipboard commands.
+#[derive(Resource)]
+struct IcedClipboard(SyncCell<BoxedClipboard>);
+
+// Iced's debug state, whose overlay is extracted and drawn on top of every frame.
+#[derive(Resource)]
+pub(crate) struct IcedDebug {
+    pub(crate) debug: Debug,
+    // Whether the overlay is toggled on, which `Debug` doesn't expose.
+    pub(crate) enabled: bool,
+}
+
+// The renderer used to lay out and draw UIs with a given theme type, and its settings.
+struct IcedRenderer<Theme> {
+    renderer: iced_wgpu::Renderer<Theme>,
+    settings: Settings,
+}
+
+impl<Theme> IcedRenderer<Theme> {
+    fn new(device: &RenderDevice, settings: Settings) -> Self {
+        // This renderer is only used for layout; the render node creates a backend for the
+        // texture format of each surface it draws to.
         let format = wgpu::TextureFormat::Bgra8UnormSrgb;
 
         Self {
             renderer: iced_wgpu::Renderer::new(iced_wgpu::Backend::new(
-                device,
+                device.wgpu_device(),
                 settings,
                 format,
             )),
-            debug: Debug::new(),
-            clipboard: iced_native::clipboard::Null,
+            settings,
         }
     }
 }
 
-#[derive(Resource, Clone)]
-struct IcedResource(Arc<Mutex<IcedProps>>);
-
-impl IcedResource {
-    fn lock(&self) -> std::sync::LockResult<std::sync::MutexGuard<IcedProps>> {
-        self.0.lock()
-    }
+/// The interface state of the UIs, like focus, scroll positions and text input contents.
+///
+/// The state of a UI is kept for as long as it is displayed. Use this non-send resource to
+/// reset the state of UIs that are no longer shown, or enable automatic eviction with
+/// [`IcedPlugin::with_cache_eviction`].
+///
+/// Iced stores widget state as `Box<dyn Any>`, which isn't `Send`, so the cache and the
+/// systems using [`IcedContext`] stay on the main thread. This doesn't hold back pipelined
+/// rendering: only the drawn primitives are extracted, and the render world never touches
+/// the cache.
+/// ```ignore
+/// fn close_menu(mut cache: NonSendMut<IcedCache>) {
+///     cache.clear::<MenuMessage>();
+/// }
+/// ```
+#[derive(Default)]
+pub struct IcedCache {
+    // The interface of every display call of a message type in a window.
+    cache: HashMap<(TypeId, Entity, CacheSlot), CacheEntry>,
+    // The number of display calls of each message type in each window this frame.
+    displays: HashMap<(TypeId, Entity), usize>,
+    // The number of draw calls, which reuse the interfaces of the display calls in order.
+    draws: HashMap<(TypeId, Entity), usize>,
+    commands: HashMap<TypeId, Box<dyn Any>>,
+    // The buffer that display calls collect events in, kept to reuse its allocation.
+    events: Vec<iced::Event>,
+    // The timers of the subscriptions, by message type, period and id.
+    timers: HashMap<(TypeId, Duration, u64), Timer>,
+    // The last frame a modal UI was displayed in each window.
+    modals: HashMap<Entity, u64>,
+    // The tooltip requested for each message type.
+    tooltips: HashMap<TypeId, tooltip::TooltipState>,
+    frame: u64,
+    evict_after: Option<u64>,
 }
 
-impl From<IcedProps> for IcedResource {
-    fn from(value: IcedProps) -> Self {
-        Self(Arc::new(Mutex::new(value)))
-    }
+struct CacheEntry {
+    cache: Option<user_interface::Cache>,
+    output: Option<DisplayOutput>,
+    // The `Retained` interface of a `display_retained` call.
+    retained: Option<Box<dyn Any>>,
+    invalidated: bool,
+    last_displayed: u64,
 }
 
-fn setup_pipeline(graph: &mut RenderGraph) {
-    graph.add_node(render::ICED_PASS, IcedNode::new());
+// The timer of a subscription of `IcedContext::subscribe`.
+struct Timer {
+    // When it fires next, in the time of the app.
+    next: Duration,
+    // The last frame it was subscribed to.
+    subscribed: u64,
+}
 
-    graph.add_node_edge(
-        bevy_render::main_graph::node::CAMERA_DRIVER,
-        render::ICED_PASS,
-    );
+// An interface kept alive across frames by `IcedContext::display_retained`.
+struct Retained<M: 'static, Theme: 'static> {
+    ui: UserInterface<'static, M, iced_wgpu::Renderer<Theme>>,
+    key: u64,
+    bounds: iced_native::Size,
+    // Whether the interface produced messages or asked to be rebuilt.
+    outdated: bool,
 }
 
-#[doc(hidden)]
-#[derive(Default)]
-pub struct IcedCache {
-    cache: HashMap<TypeId, Option<user_interface::Cache>>,
+// Identifies the interfaces displayed in a window with the same message type.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum CacheSlot {
+    // The n-th display call of the frame.
+    Call(usize),
+    // The UI of an `IcedUi` entity.
+    Entity(Entity),
 }
 
 impl IcedCache {
-    fn get<M: Any>(&mut self) -> &mut Option<user_interface::Cache> {
-        let id = TypeId::of::<M>();
-        if !self.cache.contains_key(&id) {
-            self.cache.insert(id, Some(Default::default()));
+    // Returns the entry of the next display (or draw) call of `M` in the window, or the entry
+    // of the entity that owns the UI.
+    fn entry<M: Any>(
+        &mut self,
+        window: Entity,
+        owner: Option<Entity>,
+        draw: bool,
+    ) -> &mut CacheEntry {
+        let slot = match owner {
+            Some(owner) => CacheSlot::Entity(owner),
+            None => {
+                let calls = if draw {
+                    &mut self.draws
+                } else {
+                    &mut self.displays
+                };
+                let displays = calls.entry((TypeId::of::<M>(), window)).or_default();
+                *displays += 1;
+                CacheSlot::Call(*displays - 1)
+            }
+        };
+        let id = (TypeId::of::<M>(), window, slot);
+        let entry = self.cache.entry(id).or_insert_with(|| CacheEntry {
+            cache: Some(Default::default()),
+            output: None,
+            retained: None,
+            invalidated: false,
+            last_displayed: 0,
+        });
+        entry.last_displayed = self.frame;
+        entry
+    }
+
+    // Whether a modal UI was displayed in the window this frame or the last one.
+    fn modal_shown(&self, window: Entity) -> bool {
+        self.modals
+            .get(&window)
+            .map_or(false, |frame| frame + 1 >= self.frame)
+    }
+
+    pub(crate) fn new_frame(&mut self) {
+        self.displays.clear();
+        self.draws.clear();
+        self.frame += 1;
+        // Subscriptions end when they aren't renewed.
+        let frame = self.frame;
+        self.timers.retain(|_, timer| timer.subscribed + 1 >= frame);
+        if let Some(frames) = self.evict_after {
+            self.cache
+                .retain(|_, entry| frame - entry.last_displayed <= frames);
         }
-        self.cache.get_mut(&id).unwrap()
+    }
+
+    /// Drop the interface state of all UIs with the message type `M`, in every window.
+    pub fn clear<M: Any>(&mut self) {
+        let message = TypeId::of::<M>();
+        self.cache
+            .retain(|(message_type, ..), _| *message_type != message);
+        self.timers
+            .retain(|(message_type, ..), _| *message_type != message);
+        self.tooltips.remove(&message);
+    }
+
+    /// Rebuild the retained interfaces of UIs with the message type `M` on their next display,
+    /// keeping their state. See [`IcedContext::display_retained`].
+    pub fn invalidate<M: Any>(&mut self) {
+        let message = TypeId::of::<M>();
+        for ((message_type, ..), entry) in self.cache.iter_mut() {
+            if *message_type == message {
+                entry.invalidated = true;
+            }
+        }
+    }
+
+    /// Drop the interface state of all UIs.
+    pub fn clear_all(&mut self) {
+        self.cache.clear();
+        self.timers.clear();
+        self.tooltips.clear();
+    }
+
+    /// Drop the interface state of UIs that weren't displayed for the given number of frames,
+    /// or never if `None`.
+    pub fn set_eviction(&mut self, frames: Option<u64>) {
+        self.evict_after = frames;
+    }
+
+    // Drops the interface state of an entity's UI.
+    fn remove_owner<M: Any>(&mut self, owner: Entity) {
+        let message = TypeId::of::<M>();
+        self.cache.retain(|(message_type, _, slot), _| {
+            *message_type != message || *slot != CacheSlot::Entity(owner)
+        });
+    }
+
+    fn commands<M: Send + 'static>(&mut self) -> &mut CommandQueue<M> {
+        self.commands
+            .entry(TypeId::of::<M>())
+            .or_insert_with(|| Box::<CommandQueue<M>>::default())
+            .downcast_mut()
+            .unwrap()
     }
 }
 
 /// Settings used to independently customize Iced rendering.
-#[derive(Clone, Resource)]
-pub struct IcedSettings {
+///
+/// Insert them as a component on a window or [`IcedRenderTarget`] to override this resource for
+/// its UIs, e.g. to give a tool palette another theme and scale factor than the game window.
+/// The renderer settings, text scale and debug overlay are shared by all windows and only read
+/// from the resource.
+///
+/// The fields of Bevy types are reflected, and the theme and text color of the default settings
+/// through [`IcedReflectedStyle`], to edit them in reflection-based tools.
+#[derive(Clone, Resource, Component, Reflect)]
+pub struct IcedSettings<Theme = iced_wgpu::Theme> {
     /// The scale factor to use for rendering Iced elements.
     /// Setting this to `None` defaults to using the `Window`s scale factor.
     pub scale_factor: Option<f64>,
     /// The theme to use for rendering Iced elements.
-    pub theme: iced_wgpu::Theme,
+    #[reflect(ignore)]
+    pub theme: Theme,
     /// The style to use for rendering Iced elements.
+    #[reflect(ignore)]
     pub style: iced_native::renderer::Style,
+    /// Draw Iced's debug overlay, with the time spent on each stage of the UIs, on top of every
+    /// window. The metrics are only recorded with the `debug` feature.
+    pub show_debug_overlay: bool,
+    /// The settings of the Iced renderer, like the default font and text size. The renderer is
+    /// rebuilt when they change.
+    #[reflect(ignore)]
+    pub renderer: Settings,
+    /// The region of each window that its UIs are laid out in and clipped to, e.g. around the
+    /// game view of an editor, or `None` for the whole window.
+    #[reflect(ignore)]
+    pub viewport_rect: Option<IcedViewportRect>,
+    /// Lay out window UIs at this fixed size, scaled uniformly to fit each window and centered
+    /// with empty bars on the sides, so the layout is the same at any window size. Replaces
+    /// `scale_factor` for windows, and is fitted into the `viewport_rect` and the safe area.
+    /// Sizes smaller than a pixel are ignored.
+    #[reflect(ignore)]
+    pub design_resolution: Option<iced_native::Size>,
 }
 
-impl IcedSettings {
-    /// Set the `scale_factor` used to render Iced elements.
-    pub fn set_scale_factor(&mut self, factor: impl Into<Option<f64>>) {
-        self.scale_factor = factor.into();
+/// The insets from the edges of the windows that are hidden by camera cutouts, rounded corners
+/// or home indicators on phones.
+///
+/// Bevy doesn't report the safe area of the screen, so set the insets from the platform APIs,
+/// e.g. `WindowInsets` on Android or `safeAreaInsets` on iOS. While `apply` is enabled, window
+/// UIs are laid out inside the safe area; otherwise UIs can read the insets to pad only some
+/// elements, like a HUD over a full-screen background.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq)]
+pub struct IcedSafeArea {
+    /// The insets in logical pixels of the window.
+    pub insets: iced_native::Padding,
+    /// Whether window UIs are laid out inside the safe area.
+    pub apply: bool,
+}
+
+impl IcedSafeArea {
+    // Shrinks a rectangle in the coordinates of the UIs of the window by the insets.
+    fn shrink(&self, area: Rectangle, window: &Window, bounds: iced_native::Size) -> Rectangle {
+        if !self.apply {
+            return area;
+        }
+        let iced_native::Padding {
+            top,
+            right,
+            bottom,
+            left,
+        } = self.insets;
+        let (scale_x, scale_y) = (
+            bounds.width / window.width(),
+            bounds.height / window.height(),
+        );
+        let safe = Rectangle {
+            x: left * scale_x,
+            y: top * scale_y,
+            width: (bounds.width - (left + right) * scale_x).max(0.0),
+            height: (bounds.height - (top + bottom) * scale_y).max(0.0),
+        };
+        area.intersection(&safe).unwrap_or(Rectangle::new(
+            Point::new(safe.x, safe.y),
+            iced_native::Size::ZERO,
+        ))
     }
 }
 
-impl Default for IcedSettings {
-    fn default() -> Self {
+/// A region of a window, see [`IcedSettings::viewport_rect`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IcedViewportRect {
+    /// A rectangle in physical pixels, from the top-left corner of the window.
+    Physical(Rectangle<u32>),
+    /// A rectangle in logical pixels, from the top-left corner of the window.
+    Logical(Rectangle),
+}
+
+impl IcedViewportRect {
+    // The rectangle in the coordinates of UIs drawn with the scale factor, clamped to the window.
+    fn to_ui(self, window: &Window, scale_factor: f64) -> Rectangle {
+        let physical = match self {
+            Self::Physical(rect) => Rectangle {
+                x: rect.x as f32,
+                y: rect.y as f32,
+                width: rect.width as f32,
+                height: rect.height as f32,
+            },
+            Self::Logical(rect) => rect * window.scale_factor() as f32,
+        };
+        let size = iced_native::Size::new(
+            window.physical_width() as f32,
+            window.physical_height() as f32,
+        );
+        let physical = physical
+            .intersection(&Rectangle::with_size(size))
+            .unwrap_or(Rectangle::new(Point::ORIGIN, iced_native::Size::ZERO));
+        physical * (1.0 / scale_factor as f32)
+    }
+}
+
+impl<Theme> IcedSettings<Theme> {
+    /// Creates settings with the given theme and the default scale factor and style.
+    pub fn new(theme: Theme) -> Self {
         Self {
             scale_factor: None,
-            theme: iced_wgpu::Theme::Dark,
+            theme,
             style: iced_native::renderer::Style {
                 text_color: iced_native::Color::WHITE,
             },
+            show_debug_overlay: false,
+            renderer: Settings::default(),
+            viewport_rect: None,
+            design_resolution: None,
         }
     }
+
+    /// Set the `scale_factor` used to render Iced elements.
+    pub fn set_scale_factor(&mut self, factor: impl Into<Option<f64>>) {
+        self.scale_factor = factor.into();
+    }
+
+    /// Use the given font for text that doesn't specify one, or the built-in font if `None`.
+    ///
+    /// Owned font data is leaked, since the renderer needs it for the lifetime of the app, but
+    /// only the first time a font is set, so switching between fonts doesn't leak more of them.
+    pub fn set_default_font(&mut self, font: Option<Cow<'static, [u8]>>) {
+        self.renderer.default_font = font.map(leak_font);
+    }
+
+    /// Set the size of text that doesn't specify one, e.g. from an accessibility option.
+    pub fn set_default_text_size(&mut self, size: f32) {
+        self.renderer.default_text_size = size;
+    }
+
+    // The design resolution, unless it's too small to be scaled to a window.
+    pub(crate) fn design_size(&self) -> Option<iced_native::Size> {
+        self.design_resolution
+            .filter(|size| size.width >= 1.0 && size.height >= 1.0)
+    }
+
+    /// Smooth the edges of meshes with multisampling, see [`IcedPlugin::with_antialiasing`].
+    pub fn set_antialiasing(&mut self, antialiasing: Option<iced_wgpu::Antialiasing>) {
+        self.renderer.antialiasing = antialiasing;
+    }
+}
+
+impl Default for IcedSettings {
+    fn default() -> Self {
+        Self::new(iced_wgpu::Theme::Dark)
+    }
+}
+
+/// Renders the UI of this entity into an [`Image`] instead of a window surface.
+///
+/// Spawn an entity with this component and pass it to [`IcedContext::display_in`] to draw
+/// an interface into the image, e.g. for in-world screens. The image must be created with
+/// `TextureUsages::RENDER_ATTACHMENT`.
+///
+/// Render targets receive no window input; the UI is drawn after the cameras, so cameras
+/// sampling the image will show the interface from the previous frame.
+#[derive(Component, Clone, Debug)]
+pub struct IcedRenderTarget(pub Handle<Image>);
+
+/// Clears this window or [`IcedRenderTarget`] to a color before its UIs are drawn, replacing
+/// whatever the cameras rendered to it.
+///
+/// With a transparent color on a transparent window, everything outside the UIs is see-through,
+/// for desktop overlays like stream widgets. Iced blends with premultiplied alpha, so the
+/// window should composite that way:
+/// ```ignore
+/// commands.spawn((
+///     Window {
+///         transparent: true,
+///         decorations: false,
+///         composite_alpha_mode: CompositeAlphaMode::PreMultiplied,
+///         ..default()
+///     },
+///     IcedClearColor(Color::NONE),
+/// ));
+/// ```
+///
+/// Windows that also show a camera don't need it: clear them with a transparent `ClearColor`
+/// instead. Render targets are otherwise drawn over their last contents.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct IcedClearColor(pub Color);
+
+/// Overrides [`IcedSettings::scale_factor`] for the UIs of this window, e.g. to make them
+/// larger on a window shown on a TV.
+///
+/// Cursor and touch positions are mapped to the UIs with the scale factor of their window, so
+/// windows with different scale factors receive input at the right positions.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct IcedScaleFactor(pub f64);
+
+/// Lets UIs be displayed in the viewport of this camera, e.g. a HUD in each half of a split
+/// screen.
+///
+/// Pass the camera entity to [`IcedContext::display_in`] like a window, or give [`IcedUi`]
+/// components a `RenderLayers` to display them in every camera with this component that sees
+/// one of their layers, so a minimap camera without those layers shows no HUD. The UIs are laid
+/// out in the viewport of the camera; cameras that render to images aren't supported.
+///
+/// The UIs of a camera receive the mouse input of its window while the cursor is in its
+/// viewport, and the keyboard and gamepad input assigned to it, so each player of a split
+/// screen controls their own UI. No camera takes the keyboard unless it's given to it:
+/// ```ignore
+/// commands.spawn((
+///     Camera3dBundle { camera: left, ..default() },
+///     IcedCamera::default().with_keyboard(),
+/// ));
+/// commands.spawn((
+///     Camera3dBundle { camera: right, ..default() },
+///     IcedCamera::default().with_gamepad(Gamepad::new(0)),
+/// ));
+/// ```
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct IcedCamera {
+    /// Whether the UIs of the camera receive the keyboard input of the window. Off by default,
+    /// so that typing isn't sent to the UIs of every camera at once.
+    pub keyboard: bool,
+    /// The gamepad whose navigation input goes to the UIs of the camera instead of the focused
+    /// window, see [`IcedGamepadNavigation`]. Requires the `gamepad` feature.
+    #[cfg(feature = "gamepad")]
+    pub gamepad: Option<bevy_input::gamepad::Gamepad>,
+}
+
+impl IcedCamera {
+    /// Give the keyboard input of the window to the UIs of the camera, e.g. for the viewport
+    /// of the player using the keyboard and mouse.
+    pub fn with_keyboard(mut self) -> Self {
+        self.keyboard = true;
+        self
+    }
+
+    /// Give the navigation input of the gamepad to the UIs of the camera.
+    #[cfg(feature = "gamepad")]
+    pub fn with_gamepad(mut self, gamepad: bevy_input::gamepad::Gamepad) -> Self {
+        self.gamepad = Some(gamepad);
+        self
+    }
+}
+
+/// The text currently being composed with an input method (IME).
+///
+/// Iced text inputs can't show composition text themselves, so it is exposed here for the UI
+/// to display next to the focused input; committed text is typed into the UI directly.
+/// Bevy only sends IME events for windows that have `Window::ime_enabled` set.
+#[derive(Default, Resource, Clone, Debug)]
+pub struct IcedImePreedit {
+    /// The window the text is being composed in, if any.
+    pub window: Option<Entity>,
+    /// The text being composed.
+    pub value: String,
+    /// The byte range of the cursor within `value`, or `None` if it should be hidden.
+    pub cursor: Option<(usize, usize)>,
 }
 
-/// Result of a [`display`] pass.
+/// Notifies the plugin that the application is suspended or resumed.
+///
+/// On mobile platforms like Android, the rendering surface is destroyed while the app is in the
+/// background. Send [`IcedLifecycle::Suspended`] when that happens, so that the wgpu resources
+/// of the UI are dropped, and [`IcedLifecycle::Resumed`] once the app is back in the foreground;
+/// the resources are then recreated for the new surface on the next frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IcedLifecycle {
+    /// The application moved to the background.
+    Suspended,
+    /// The application moved back to the foreground.
+    Resumed,
+}
+
+/// Result of the [`display`] passes of the frame, combined over every UI and window.
 #[derive(Default, Resource)]
 pub struct IcedDisplayResult {
-    /// Contains all events that were captured during the pass.
+    /// Contains all events that were captured by the UIs displayed this frame.
     pub captured_events: Vec<iced_native::Event>,
-    /// Is the mouse cursor over some interactive element?
+    /// Is the mouse cursor over some interactive element of any UI?
+    ///
+    /// Until the UIs are displayed again, this is hit-tested against the previous frame of each
+    /// window at the current cursor position, so systems that run before [`IcedSet::Display`]
+    /// don't read a stale value.
     pub wants_pointer_input: bool,
+    /// Does a widget of any UI, like a text input, currently have keyboard focus?
+    pub wants_keyboard_input: bool,
+    /// The mouse interaction of the widget under the cursor in each window.
+    pub interactions: HashMap<Entity, iced_native::mouse::Interaction>,
+    // Whether a UI was displayed since the start of the frame, after which the results of the
+    // display calls are combined instead of replaced.
+    displayed: bool,
 }
 
-// An atomic flag for updating the draw state.
-#[derive(Resource, Deref, DerefMut, Default)]
-pub(crate) struct DidDraw(std::sync::atomic::AtomicBool);
+impl IcedDisplayResult {
+    /// The mouse interaction of the widget under the cursor in a window.
+    pub fn interaction(&self, window: Entity) -> iced_native::mouse::Interaction {
+        self.interactions.get(&window).copied().unwrap_or_default()
+    }
+
+    // Starts combining the results of the display calls of a new frame.
+    pub(crate) fn new_frame(&mut self) {
+        self.displayed = false;
+    }
+
+    // Clears the results of the last frame before the first display call of this one.
+    fn begin_display(&mut self) {
+        if !std::mem::replace(&mut self.displayed, true) {
+            self.captured_events.clear();
+            self.wants_pointer_input = false;
+            self.wants_keyboard_input = false;
+            self.interactions.clear();
+        }
+    }
+}
+
+/// When the UI changes the cursor icon of a window to match the widget under the cursor.
+///
+/// The interaction in each window is also available in [`IcedDisplayResult::interaction`], for
+/// games that manage the cursor themselves.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IcedCursorPolicy {
+    /// Always show the cursor icon of the UI, resetting it to the default when idle.
+    #[default]
+    Always,
+    /// Only change the cursor icon while a widget is interacted with, and reset it afterwards.
+    NonIdle,
+    /// Never change the cursor icon.
+    Never,
+}
+
+/// Whether the cursor is used by the UIs or by the game, e.g. to look around in a first-person
+/// game.
+///
+/// Switch to [`IcedCursorMode::Ui`] when opening a menu and back to [`IcedCursorMode::Game`]
+/// when closing it, and the cursor of the primary window is released and shown, or grabbed
+/// and hidden, to match. While the cursor of a window is grabbed and hidden, whether by this
+/// resource or by the game itself, its UIs ignore the mouse and [`pointer_over_ui`] is false.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IcedCursorMode {
+    /// Leave the grab mode and the visibility of the cursor to the game.
+    #[default]
+    Unmanaged,
+    /// Release and show the cursor, to use the UIs.
+    Ui,
+    /// Grab the cursor with the given mode and hide it. Windows only supports
+    /// `CursorGrabMode::Confined` and macOS only `CursorGrabMode::Locked`.
+    Game(CursorGrabMode),
+}
+
+impl IcedCursorMode {
+    /// Switches between [`IcedCursorMode::Ui`] and [`IcedCursorMode::Game`], grabbing the cursor
+    /// with `grab_mode` when switching to the game.
+    pub fn toggle(&mut self, grab_mode: CursorGrabMode) {
+        *self = match self {
+            Self::Game(_) => Self::Ui,
+            Self::Ui | Self::Unmanaged => Self::Game(grab_mode),
+        };
+    }
+}
+
+/// Chooses the cursor icon shown for each UI interaction.
+///
+/// Replace this resource to use your own icons:
+/// ```ignore
+/// app.insert_resource(IcedCursorIcons::new(|interaction| match interaction {
+///     Interaction::Pointer => CursorIcon::Crosshair,
+///     _ => CursorIcon::Default,
+/// }));
+/// ```
+/// Bevy windows only support the system cursors. To show a custom cursor image, hide the
+/// cursor, set [`IcedCursorPolicy::Never`] and draw the image for
+/// [`IcedDisplayResult::interaction`] yourself.
+#[derive(Resource)]
+pub struct IcedCursorIcons(
+    Box<dyn Fn(iced_native::mouse::Interaction) -> CursorIcon + Send + Sync>,
+);
+
+impl IcedCursorIcons {
+    /// Creates a mapping from interactions to cursor icons.
+    pub fn new(
+        icon: impl Fn(iced_native::mouse::Interaction) -> CursorIcon + Send + Sync + 'static,
+    ) -> Self {
+        Self(Box::new(icon))
+    }
+
+    /// The cursor icon for the given interaction.
+    pub fn icon(&self, interaction: iced_native::mouse::Interaction) -> CursorIcon {
+        (self.0)(interaction)
+    }
+}
+
+impl Default for IcedCursorIcons {
+    fn default() -> Self {
+        Self::new(conversions::cursor_icon)
+    }
+}
+
+/// Which kinds of input are forwarded to a UI.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputForwarding {
+    /// Forward key presses and typed characters.
+    pub keyboard: bool,
+    /// Forward cursor movement and mouse buttons.
+    pub mouse: bool,
+    /// Forward mouse wheel scrolling.
+    pub wheel: bool,
+    /// Forward touch input.
+    pub touch: bool,
+}
+
+impl InputForwarding {
+    /// Forward all input.
+    pub const ALL: Self = Self {
+        keyboard: true,
+        mouse: true,
+        wheel: true,
+        touch: true,
+    };
+
+    /// Forward no input, e.g. for a UI that is only displayed.
+    pub const NONE: Self = Self {
+        keyboard: false,
+        mouse: false,
+        wheel: false,
+        touch: false,
+    };
+
+    fn forwards(&self, event: &iced_native::Event) -> bool {
+        use iced_native::{mouse, Event};
+        match event {
+            Event::Keyboard(_) => self.keyboard,
+            Event::Mouse(mouse::Event::WheelScrolled { .. }) => self.wheel,
+            Event::Mouse(_) => self.mouse,
+            Event::Touch(_) => self.touch,
+            _ => true,
+        }
+    }
+}
+
+impl Default for InputForwarding {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// Configures which input is forwarded to the UIs, globally or per message type.
+///
+/// Input that a UI doesn't receive is left for other UIs displayed in the same window,
+/// so e.g. a HUD can ignore the keyboard while a menu gets all input:
+/// ```ignore
+/// let mut config = IcedInputConfig::default();
+/// config.set_for::<HudMessage>(InputForwarding {
+///     keyboard: false,
+///     ..InputForwarding::ALL
+/// });
+/// app.insert_resource(config);
+/// ```
+#[derive(Resource, Clone, Debug, Default)]
+pub struct IcedInputConfig {
+    /// The input forwarded to UIs of message types without their own setting.
+    pub global: InputForwarding,
+    overrides: HashMap<TypeId, InputForwarding>,
+}
+
+impl IcedInputConfig {
+    /// Set the input forwarded to UIs with the message type `M`.
+    pub fn set_for<M: Event>(&mut self, forwarding: InputForwarding) {
+        self.overrides.insert(TypeId::of::<M>(), forwarding);
+    }
+
+    /// Use the global setting for UIs with the message type `M` again.
+    pub fn reset_for<M: Event>(&mut self) {
+        self.overrides.remove(&TypeId::of::<M>());
+    }
+
+    /// The input forwarded to UIs with the message type `M`.
+    pub fn for_message<M: Event>(&self) -> InputForwarding {
+        self.overrides
+            .get(&TypeId::of::<M>())
+            .copied()
+            .unwrap_or(self.global)
+    }
+}
+
+/// A function that returns the event to pass to the UIs of a window, possibly changed, or `None`
+/// to drop it.
+pub type IcedEventFilter = Box<dyn FnMut(Entity, iced::Event) -> Option<iced::Event> + Send + Sync>;
+
+/// Filters that inspect, change or drop Iced events before any UI receives them.
+///
+/// The filters run in the order they were added, once per event and window, after the input of
+/// the frame was processed. Events they drop don't wake an [idle](IcedIdle) app either.
+/// ```ignore
+/// let mut filters = IcedEventFilters::default();
+/// // Invert scrolling in all UIs.
+/// filters.add(|_window, event| match event {
+///     iced::Event::Mouse(mouse::Event::WheelScrolled {
+///         delta: mouse::ScrollDelta::Lines { x, y },
+///     }) => Some(iced::Event::Mouse(mouse::Event::WheelScrolled {
+///         delta: mouse::ScrollDelta::Lines { x: -x, y: -y },
+///     })),
+///     event => Some(event),
+/// });
+/// app.insert_resource(filters);
+/// ```
+#[derive(Resource, Default)]
+pub struct IcedEventFilters(Vec<IcedEventFilter>);
+
+impl IcedEventFilters {
+    /// Add a filter that runs after the existing ones.
+    pub fn add(
+        &mut self,
+        filter: impl FnMut(Entity, iced::Event) -> Option<iced::Event> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.0.push(Box::new(filter));
+        self
+    }
+
+    /// Remove all the filters.
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Whether there are no filters.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub(crate) fn apply(&mut self, window: Entity, event: iced::Event) -> Option<iced::Event> {
+        self.0
+            .iter_mut()
+            .try_fold(event, |event, filter| filter(window, event))
+    }
+}
+
+/// The order in which the UIs of different message types are stacked in a window.
+///
+/// UIs with a higher z are drawn on top; UIs with the same z, which is 0 by default, are drawn
+/// in the order they are displayed in.
+/// ```ignore
+/// let mut layers = IcedLayerOrder::default();
+/// layers.set::<HudMessage>(0);
+/// layers.set::<MenuMessage>(10);
+/// layers.set::<ModalMessage>(20);
+/// app.insert_resource(layers);
+/// ```
+#[derive(Resource, Clone, Debug, Default)]
+pub struct IcedLayerOrder(HashMap<TypeId, i32>);
+
+impl IcedLayerOrder {
+    /// Set the z-order of UIs with the message type `M`.
+    pub fn set<M: Event>(&mut self, z: i32) {
+        self.0.insert(TypeId::of::<M>(), z);
+    }
+
+    /// The z-order of UIs with the message type `M`.
+    pub fn get<M: Event>(&self) -> i32 {
+        self.0.get(&TypeId::of::<M>()).copied().unwrap_or_default()
+    }
+}
+
+/// Makes the UIs at or above a z-order of [`IcedLayerOrder`] modal: while one of them is
+/// displayed in a window, a translucent scrim is drawn over the whole window under it, and the
+/// UIs below stop receiving input, so modal dialogs block the rest of the interface.
+/// ```ignore
+/// layers.set::<ModalMessage>(20);
+/// app.insert_resource(layers).insert_resource(IcedModalLayer {
+///     z: Some(20),
+///     scrim: iced::Color::from_rgba(0.0, 0.0, 0.0, 0.6),
+/// });
+/// ```
+///
+/// UIs displayed before the modal one in a frame find out from the previous frame that it is
+/// shown, so they stay blocked for one frame after it closes.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct IcedModalLayer {
+    /// The lowest z-order of the modal UIs, or `None` for no modal layer.
+    pub z: Option<i32>,
+    /// The color of the scrim, whose alpha sets how much the UIs and the game behind it are
+    /// dimmed.
+    pub scrim: iced::Color,
+}
+
+impl Default for IcedModalLayer {
+    fn default() -> Self {
+        Self {
+            z: None,
+            scrim: iced::Color::from_rgba(0.0, 0.0, 0.0, 0.5),
+        }
+    }
+}
+
+/// Which input captured by the UI is removed from Bevy's `Input` resources.
+///
+/// When enabled, mouse buttons and keys that a widget reacted to no longer show up as pressed in
+/// `Input<MouseButton>` and `Input<KeyCode>`, so game systems that poll those resources ignore
+/// clicks on the UI. Run such systems after [`IcedSet::ConsumeInput`]. Nothing is consumed
+/// by default.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InputConsumptionPolicy {
+    /// Consume mouse button presses and releases captured by the UI.
+    pub mouse_buttons: bool,
+    /// Consume key presses captured by the UI.
+    pub keyboard: bool,
+}
+
+impl InputConsumptionPolicy {
+    /// Consume all captured input.
+    pub const ALL: Self = Self {
+        mouse_buttons: true,
+        keyboard: true,
+    };
+}
+
+/// How close in time and space mouse presses have to be to count as a double or triple click.
+///
+/// Iced widgets, like `text_input` selecting a word, detect double and triple clicks from the
+/// presses they receive, but only when a press happens at exactly the same cursor position as
+/// the previous one, within 300 ms. Presses that are closer than `max_distance` to the previous
+/// one within `interval` are moved to its position, so that clicks still count when the mouse
+/// moved by a pixel or two. Intervals longer than the 300 ms of Iced have no effect.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct IcedDoubleClick {
+    /// The longest time between two presses of a double click.
+    pub interval: Duration,
+    /// The farthest the cursor can move between two presses of a double click, in logical
+    /// pixels.
+    pub max_distance: f32,
+}
+
+impl Default for IcedDoubleClick {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(300),
+            max_distance: 4.0,
+        }
+    }
+}
+
+/// Which key codes the keys pressed on the keyboard are reported as to the UIs, e.g. for
+/// shortcuts like Ctrl+Z.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IcedKeyMapping {
+    /// The key that types the same letter or digit in the current keyboard layout, so Ctrl+Z
+    /// is the key labeled Z on AZERTY and Dvorak keyboards too. The layout is learned from the
+    /// characters typed by the keys, and until a key typed one, the key code reported by Bevy
+    /// is used, which follows the layout on most desktop platforms.
+    #[default]
+    Logical,
+    /// The key at the same position on a US QWERTY keyboard, e.g. for WASD-style bindings.
+    /// Only available on Windows and Linux; other platforms use the key code reported by Bevy.
+    Physical,
+}
+
+/// Configures how mouse wheels and trackpads scroll the UIs.
+///
+/// Mouse wheels that scroll by lines are reported as lines, which Iced widgets turn into a
+/// distance that suits them, and trackpads as pixels.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct IcedScrollConfig {
+    /// Multiplies the lines scrolled by mouse wheels.
+    pub line_multiplier: f32,
+    /// Multiplies the pixels scrolled by trackpads and high-precision wheels.
+    pub pixel_multiplier: f32,
+    /// Multiplies the horizontal scroll distance.
+    pub x_multiplier: f32,
+    /// Multiplies the vertical scroll distance.
+    pub y_multiplier: f32,
+    /// Whether to scroll up when the platform scrolls down, and the reverse.
+    pub invert_y: bool,
+    /// Whether vertical scrolling becomes horizontal while shift is held, and the reverse.
+    /// macOS already does this for mouse wheels, so it is usually left off there.
+    pub swap_axes_with_shift: bool,
+}
+
+impl IcedScrollConfig {
+    // Applies the per-axis settings to a scroll distance, in the units reported by Bevy.
+    pub(crate) fn apply(&self, x: f32, y: f32, shift: bool) -> (f32, f32) {
+        let (x, y) = (x, if self.invert_y { -y } else { y });
+        let (x, y) = if shift && self.swap_axes_with_shift {
+            (y, x)
+        } else {
+            (x, y)
+        };
+        (x * self.x_multiplier, y * self.y_multiplier)
+    }
+}
+
+impl Default for IcedScrollConfig {
+    fn default() -> Self {
+        Self {
+            line_multiplier: 1.0,
+            pixel_multiplier: 1.0,
+            x_multiplier: 1.0,
+            y_multiplier: 1.0,
+            invert_y: false,
+            swap_axes_with_shift: false,
+        }
+    }
+}
+
+/// How the key presses of held keys are repeated, e.g. to keep deleting text while backspace
+/// is held.
+///
+/// While enabled, the repeated presses that some platforms send are replaced by presses sent
+/// every `interval` once a key was held for `delay`, so keys repeat the same way everywhere.
+/// Only key presses are repeated; typed characters are repeated as reported by the platform.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IcedKeyRepeat {
+    /// Whether held keys are repeated.
+    pub enabled: bool,
+    /// How long a key is held before it repeats.
+    pub delay: Duration,
+    /// The time between two repeated presses.
+    pub interval: Duration,
+}
+
+impl Default for IcedKeyRepeat {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            delay: Duration::from_millis(500),
+            interval: Duration::from_millis(33),
+        }
+    }
+}
+
+/// The areas covered by opaque UI elements in each window, as drawn in the previous frame.
+///
+/// Useful to avoid casting click rays or showing tooltips underneath UI panels. Rectangles are
+/// in the logical coordinates of the UI, with the origin in the top-left corner of the window.
+#[derive(Default, Resource, Debug)]
+pub struct IcedOcclusionRects {
+    rects: HashMap<Entity, Vec<Rectangle>>,
+    pending: HashMap<Entity, Vec<Rectangle>>,
+}
+
+impl IcedOcclusionRects {
+    /// The occluded areas of the given window.
+    pub fn get(&self, window: Entity) -> &[Rectangle] {
+        self.rects
+            .get(&window)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The occluded areas of every window.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &[Rectangle])> {
+        self.rects
+            .iter()
+            .map(|(window, rects)| (*window, rects.as_slice()))
+    }
+
+    /// Is `point` covered by an opaque UI element in the given window?
+    pub fn contains(&self, window: Entity, point: Point) -> bool {
+        self.get(window).iter().any(|rect| rect.contains(point))
+    }
+
+    pub(crate) fn swap(&mut self) {
+        std::mem::swap(&mut self.rects, &mut self.pending);
+        self.pending.clear();
+    }
+}
 
 /// The context for interacting with Iced. Add this as a parameter to your system.
 /// ```no_run
@@ -231,84 +1722,1155 @@ pub(crate) struct DidDraw(std::sync::atomic::AtomicBool);
 ///
 /// `IcedContext<T>` requires an event system to be defined in the [`App`].
 /// Do so by invoking `app.add_event::<T>()` when constructing your App.
+///
+/// Each window keeps its own interface state, so the same message type can be
+/// displayed independently in several windows with [`IcedContext::display_in`].
+/// Displaying several elements in a window, from one or more systems, draws each of them as
+/// a separate layer on top of the previous ones; they keep their state as long as they are
+/// displayed in the same order every frame.
+///
+/// The `Theme` parameter must match the theme type of the [`IcedPlugin`].
+///
+/// Without a window or a render device, like on a dedicated server or in tests, display calls
+/// do nothing, so the same systems can run there.
+///
+/// Systems with this parameter run on the main thread, see [`IcedCache`].
 #[derive(SystemParam)]
-pub struct IcedContext<'w, 's, Message: Event> {
-    viewport: Res<'w, ViewportResource>,
-    props: Res<'w, IcedResource>,
-    settings: Res<'w, IcedSettings>,
-    windows: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+pub struct IcedContext<'w, 's, Message: Event, Theme = iced_wgpu::Theme>
+where
+    Theme: Send + Sync + 'static,
+{
+    viewports: Res<'w, ViewportResource>,
+    clipboard: ResMut<'w, IcedClipboard>,
+    frames: ResMut<'w, IcedFrames>,
+    renderer: Option<NonSendMut<'w, IcedRenderer<Theme>>>,
+    settings: Res<'w, IcedSettings<Theme>>,
+    window_settings: Query<'w, 's, Ref<'static, IcedSettings<Theme>>>,
+    windows: Query<'w, 's, &'static Window>,
+    primary_window: Query<'w, 's, Entity, With<PrimaryWindow>>,
+    cameras: Query<'w, 's, (Entity, &'static Camera, &'static GlobalTransform)>,
+    iced_cameras: Query<'w, 's, (), With<IcedCamera>>,
+    transforms: Query<'w, 's, &'static GlobalTransform>,
     events: ResMut<'w, IcedEventQueue>,
+    synthetic: ResMut<'w, IcedSyntheticInput>,
+    input_config: Res<'w, IcedInputConfig>,
+    safe_area: Res<'w, IcedSafeArea>,
+    layer_order: Res<'w, IcedLayerOrder>,
+    modal_layer: Res<'w, IcedModalLayer>,
+    tooltip_settings: Res<'w, IcedTooltipSettings>,
     cache_map: NonSendMut<'w, IcedCache>,
+    time: Res<'w, Time>,
     messages: EventWriter<'w, Message>,
-    did_draw: ResMut<'w, DidDraw>,
     #[cfg(feature = "touch")]
     touches: Res<'w, Touches>,
+    #[cfg(feature = "gamepad")]
+    virtual_cursor: Res<'w, IcedVirtualCursor>,
+    occlusion: ResMut<'w, IcedOcclusionRects>,
+    captured: ResMut<'w, IcedCapturedInput>,
+    interactions: ResMut<'w, IcedInteractions>,
     result: ResMut<'w, IcedDisplayResult>,
+    timings: ResMut<'w, IcedTimings>,
+    debug: ResMut<'w, IcedDebug>,
+    idle: ResMut<'w, IcedIdle>,
+    trim: ResMut<'w, TrimCaches>,
+    glyph_warmup: ResMut<'w, GlyphWarmup>,
+    captures: ResMut<'w, IcedCaptureRequests>,
 }
 
-impl<'w, 's, M: Event> IcedContext<'w, 's, M> {
-    /// Display an [`Element`] to the screen.
-    pub fn display<'a>(&'a mut self, element: impl Into<Element<'a, M, iced_wgpu::Renderer>>) {
-        let IcedProps {
-            ref mut renderer,
-            ref mut clipboard,
-            ..
-        } = &mut *self.props.lock().unwrap();
-        let bounds = self.viewport.logical_size();
+impl<'w, 's, M: Event, Theme: Send + Sync + 'static> IcedContext<'w, 's, M, Theme> {
+    /// Display an [`Element`] to the primary window.
+    pub fn display<'a>(
+        &'a mut self,
+        element: impl Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    ) {
+        let Ok(window) = self.primary_window.get_single() else { return };
+        self.display_in(window, element);
+    }
 
-        let element = element.into();
+    /// Subscribe to a timer for this frame, sending its message if it fires.
+    ///
+    /// Call this every frame to keep the subscription active; see the [`time`] module.
+    /// Subscriptions with the same period are told apart by the variant of their messages, or
+    /// by the id given with [`time::Subscription::with_id`].
+    pub fn subscribe(&mut self, subscription: time::Subscription<M>) {
+        let now = self.time.elapsed();
+        let message = subscription.tick(time::Instant::now());
+        let id = subscription.id.unwrap_or_else(|| {
+            let mut hasher = DefaultHasher::new();
+            std::mem::discriminant(&message).hash(&mut hasher);
+            hasher.finish()
+        });
+        let frame = self.cache_map.frame;
+        let timer = self
+            .cache_map
+            .timers
+            .entry((TypeId::of::<M>(), subscription.period, id))
+            .or_insert(Timer {
+                next: now + subscription.period,
+                subscribed: frame,
+            });
+        timer.subscribed = frame;
+        if now >= timer.next {
+            timer.next += subscription.period;
+            if timer.next <= now {
+                timer.next = now + subscription.period;
+            }
+            self.messages.send(message);
+        }
+    }
 
-        let cursor_position = {
-            let window = self.windows.single();
+    /// Drop the images and text layouts cached by the renderers, e.g. when leaving a screen
+    /// that showed many transient images.
+    ///
+    /// The renderers are rebuilt before the next frame, and measure and upload what the UIs
+    /// show again. Images that aren't drawn are evicted from the GPU every frame, but the atlas
+    /// textures, and the images decoded to lay them out, only shrink when trimmed.
+    pub fn trim_caches(&mut self) {
+        **self.trim = true;
+    }
 
-            window
-                .cursor_position()
-                .map(|Vec2 { x, y }| iced_native::Point {
-                    x: x * bounds.width / window.width(),
-                    y: (window.height() - y) * bounds.height / window.height(),
-                })
-                .or_else(|| process_touch_input(self))
-                .unwrap_or(iced_native::Point::ORIGIN)
+    /// Capture the UIs displayed in the primary window this frame into an image, without what
+    /// the cameras rendered, e.g. for sharing or documentation screenshots.
+    ///
+    /// Call it after the UIs of the window are displayed. The image is ready at the end of the
+    /// frame, and its pixels a few frames later, see [`IcedCapture`].
+    /// ```ignore
+    /// if keys.just_pressed(KeyCode::F12) {
+    ///     commands.insert_resource(UiCapture(ctx.capture_to_image()));
+    /// }
+    /// ```
+    pub fn capture_to_image(&mut self) -> IcedCapture {
+        let Ok(window) = self.primary_window.get_single() else { return IcedCapture::default() };
+        self.capture_to_image_in(window)
+    }
+
+    /// Capture the UIs displayed in a window or [`IcedRenderTarget`] this frame into an image,
+    /// like [`IcedContext::capture_to_image`].
+    pub fn capture_to_image_in(&mut self, window: Entity) -> IcedCapture {
+        let capture = IcedCapture::default();
+        self.captures.push((window, capture.clone()));
+        // While idle, the UIs are captured once they're displayed again.
+        self.idle.wake();
+        capture
+    }
+
+    /// The UIs displayed in the primary window so far this frame, as an SVG document with
+    /// placeholders for images and meshes, see [`testing::export_svg`].
+    ///
+    /// Useful to review a layout, or diff it between changes, without a screenshot:
+    /// ```ignore
+    /// if keys.just_pressed(KeyCode::F11) {
+    ///     if let Some(svg) = ctx.export_svg() {
+    ///         std::fs::write("ui.svg", svg).unwrap();
+    ///     }
+    /// }
+    /// ```
+    pub fn export_svg(&self) -> Option<String> {
+        let window = self.primary_window.get_single().ok()?;
+        self.export_svg_in(window)
+    }
+
+    /// The UIs displayed in a window or [`IcedRenderTarget`] so far this frame, as an SVG
+    /// document, like [`IcedContext::export_svg`].
+    pub fn export_svg_in(&self, window: Entity) -> Option<String> {
+        let size = self.viewports.get(&window)?.logical_size();
+        let mut layers: Vec<_> = self.frames.get(&window)?.iter().collect();
+        layers.sort_by_key(|layer| layer.z);
+        Some(testing::export_svg(
+            layers.iter().flat_map(|layer| &layer.primitives),
+            size,
+        ))
+    }
+
+    /// Rasterize the glyphs of the given characters ahead of time, e.g. while loading, so the
+    /// first frame that shows a large menu doesn't hitch while the glyph caches fill.
+    ///
+    /// The characters are drawn invisibly below the UIs of every window with the next frame,
+    /// once in each of the fonts at each of the sizes.
+    /// ```ignore
+    /// ctx.warm_up_glyphs("0123456789 ABCDEFGHIJKLMNOPQRSTUVWXYZ", &[16.0, 32.0], &[Font::Default]);
+    /// ```
+    pub fn warm_up_glyphs(&mut self, characters: &str, sizes: &[f32], fonts: &[Font]) {
+        for &font in fonts {
+            for &size in sizes {
+                // Loads the font for layout as well.
+                if let Some(renderer) = &self.renderer {
+                    renderer
+                        .renderer
+                        .measure(characters, size, font, iced_native::Size::INFINITY);
+                }
+                self.glyph_warmup.push((characters.to_owned(), size, font));
+            }
+        }
+        self.idle.wake();
+    }
+
+    /// Run a widget [`Operation`] on the UI of this message type, e.g. to focus a text input
+    /// or scroll a scrollable to a position.
+    ///
+    /// The operation runs the next time an element is displayed, before input is processed,
+    /// so calling this before [`IcedContext::display`] affects the current frame. Messages
+    /// produced by the operation are sent through [`EventWriter<M>`].
+    /// ```ignore
+    /// if keys.just_pressed(KeyCode::Return) {
+    ///     ctx.operate(operation::focusable::focus(CHAT_INPUT.clone()));
+    /// }
+    /// ctx.display(chat_box());
+    /// ```
+    pub fn operate(&mut self, operation: impl Operation<M> + 'static) {
+        self.cache_map
+            .commands::<M>()
+            .push_operation(Box::new(operation));
+    }
+
+    /// Perform a [`Command`] returned by Iced widgets or your own update logic.
+    ///
+    /// Futures run on Bevy's async compute task pool, and their messages are sent through
+    /// [`EventWriter<M>`] the next time an element is displayed with this message type, like
+    /// the messages of widget operations. Window and system actions aren't supported.
+    pub fn perform(&mut self, command: Command<M>) {
+        let commands = self.cache_map.commands::<M>();
+        for action in command.actions() {
+            match action {
+                Action::Future(future) => commands.spawn(future),
+                Action::Widget(action) => commands.push_operation(action.into_operation()),
+                Action::Clipboard(iced_native::clipboard::Action::Read(read)) => {
+                    let contents = self.clipboard.0.get().read();
+                    self.messages.send(read(contents));
+                }
+                Action::Clipboard(iced_native::clipboard::Action::Write(contents)) => {
+                    self.clipboard.0.get().write(contents)
+                }
+                Action::Window(_) | Action::System(_) => {}
+            }
+        }
+    }
+
+    /// Queue an Iced event for the UIs of the primary window, as if it came from the user, e.g.
+    /// to simulate a click in a tutorial.
+    ///
+    /// The event is received by all the UIs of the window on the next frame. A
+    /// `CursorMoved` event also moves the cursor seen by the UIs, until the real cursor moves.
+    /// ```ignore
+    /// ctx.push_event(iced::Event::Mouse(mouse::Event::CursorMoved { position }));
+    /// ctx.push_event(iced::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)));
+    /// ctx.push_event(iced::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)));
+    /// ```
+    pub fn push_event(&mut self, event: iced::Event) {
+        let Ok(window) = self.primary_window.get_single() else { return };
+        self.push_event_in(window, event);
+    }
+
+    /// Queue an Iced event for the UIs of a window or [`IcedRenderTarget`], like
+    /// [`IcedContext::push_event`].
+    pub fn push_event_in(&mut self, window: Entity, event: iced::Event) {
+        self.synthetic.events.push((window, event));
+    }
+
+    /// Display an [`Element`] to the primary window, drawn with the given theme and style
+    /// instead of the ones in [`IcedSettings`].
+    ///
+    /// This lets different UI systems, like a HUD and a pause menu, use their own look.
+    pub fn display_with_theme<'a>(
+        &'a mut self,
+        element: impl Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+        theme: &Theme,
+        style: &iced_native::renderer::Style,
+    ) {
+        let Ok(window) = self.primary_window.get_single() else { return };
+        let translation = Vector::new(0.0, 0.0);
+        self.display_translated(
+            window,
+            || element,
+            translation,
+            Some((theme, style)),
+            None,
+            Phase::Both,
+            None,
+        );
+    }
+
+    /// Display an [`Element`] to the given window or [`IcedRenderTarget`] entity.
+    ///
+    /// The interface state (focus, scroll positions, etc.) is kept separately for every
+    /// window, so each window shows an independent UI.
+    pub fn display_in<'a>(
+        &'a mut self,
+        window: Entity,
+        element: impl Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    ) {
+        let translation = Vector::new(0.0, 0.0);
+        self.display_translated(
+            window,
+            || element,
+            translation,
+            None,
+            None,
+            Phase::Both,
+            None,
+        );
+    }
+
+    /// Display the element returned by `view` to the primary window, only calling `view` when
+    /// `state` changes.
+    ///
+    /// `state` should be everything the element is built from, or a hash of it. While it's the
+    /// same as in the last frame, the last frame is drawn again without building the element,
+    /// unless the UI receives input or messages, or the cursor moves.
+    /// ```ignore
+    /// ctx.display_if_changed((hud.health, hud.ammo), || hud_view(&hud));
+    /// ```
+    pub fn display_if_changed<'a, E>(&'a mut self, state: impl Hash, view: impl FnOnce() -> E)
+    where
+        E: Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    {
+        let mut hasher = DefaultHasher::new();
+        state.hash(&mut hasher);
+        let Ok(window) = self.primary_window.get_single() else { return };
+        let translation = Vector::new(0.0, 0.0);
+        self.display_translated(
+            window,
+            view,
+            translation,
+            None,
+            None,
+            Phase::Both,
+            Some(hasher.finish()),
+        );
+    }
+
+    /// Display the element that `view` builds from `deps` to the primary window, only calling
+    /// `view` when one of the dependencies changes.
+    ///
+    /// Like [`IcedContext::display_if_changed`], but with resources or components whose changes
+    /// Bevy detects, see [`IcedDependencies`], so no state is hashed. A HUD built from resources
+    /// that rarely change costs almost nothing in the other frames.
+    /// ```ignore
+    /// fn hud_system(player: Res<Player>, score: Res<Score>, mut ctx: IcedContext<UiMessage>) {
+    ///     ctx.display_with((&player, &score), |(player, score)| hud_view(player, score));
+    /// }
+    /// ```
+    pub fn display_with<'a, D, E>(&'a mut self, deps: D, view: impl FnOnce(D) -> E)
+    where
+        D: IcedDependencies,
+        E: Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    {
+        let mut hasher = DefaultHasher::new();
+        deps.hash_changes(&mut hasher);
+        let Ok(window) = self.primary_window.get_single() else { return };
+        let translation = Vector::new(0.0, 0.0);
+        self.display_translated(
+            window,
+            || view(deps),
+            translation,
+            None,
+            None,
+            Phase::Both,
+            Some(hasher.finish()),
+        );
+    }
+
+    /// Display the element returned by `view` to the primary window, keeping the built
+    /// interface alive across frames instead of building and laying it out every frame.
+    ///
+    /// `view` is only called again when `key` changes, the UI produces messages, or
+    /// [`IcedContext::invalidate`] is called, e.g. after the state it shows changed. In the
+    /// other frames input is processed and the interface is drawn with the layout of the last
+    /// one, which saves most of the cost of complex UIs. Retained elements can't borrow from
+    /// the system, so they're built from owned or cloned data.
+    /// ```ignore
+    /// if inventory.is_changed() {
+    ///     ctx.invalidate();
+    /// }
+    /// let items = inventory.items.clone();
+    /// ctx.display_retained(0, move || inventory_view(items));
+    /// ```
+    pub fn display_retained(
+        &mut self,
+        key: impl Hash,
+        view: impl FnOnce() -> Element<'static, M, iced_wgpu::Renderer<Theme>>,
+    ) {
+        let Ok(window) = self.primary_window.get_single() else { return };
+        self.display_retained_in(window, key, view);
+    }
+
+    /// Display a retained element to the given window or [`IcedRenderTarget`] entity, like
+    /// [`IcedContext::display_retained`].
+    pub fn display_retained_in(
+        &mut self,
+        window: Entity,
+        key: impl Hash,
+        view: impl FnOnce() -> Element<'static, M, iced_wgpu::Renderer<Theme>>,
+    ) {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let key = hasher.finish();
+        let build = move |entry: &mut CacheEntry,
+                          bounds: iced_native::Size,
+                          relayout: bool,
+                          renderer: &mut iced_wgpu::Renderer<Theme>| {
+            let retained = entry
+                .retained
+                .take()
+                .and_then(|retained| retained.downcast::<Retained<M, Theme>>().ok());
+            let invalidated = std::mem::take(&mut entry.invalidated);
+            match retained {
+                Some(retained) if retained.key == key && !retained.outdated && !invalidated => {
+                    if relayout || retained.bounds != bounds {
+                        retained.ui.relayout(bounds, renderer)
+                    } else {
+                        retained.ui
+                    }
+                }
+                retained => {
+                    // The widget state of the old interface carries over to the new one.
+                    let cache = match retained {
+                        Some(retained) => retained.ui.into_cache(),
+                        None => entry.cache.take().unwrap_or_default(),
+                    };
+                    UserInterface::build(view(), bounds, cache, renderer)
+                }
+            }
+        };
+        let keep = move |entry: &mut CacheEntry,
+                         ui: UserInterface<'static, M, iced_wgpu::Renderer<Theme>>,
+                         bounds: iced_native::Size,
+                         outdated: bool| {
+            entry.retained = Some(Box::new(Retained::<M, Theme> {
+                ui,
+                key,
+                bounds,
+                outdated,
+            }));
         };
+        let translation = Vector::new(0.0, 0.0);
+        self.display_interface(
+            window,
+            build,
+            keep,
+            translation,
+            None,
+            None,
+            Phase::Both,
+            Some(key),
+        );
+    }
 
-        let mut messages = Vec::<M>::new();
-        let cache_entry = self.cache_map.get::<M>();
-        let cache = cache_entry.take().unwrap();
-        let mut ui = UserInterface::build(element, bounds, cache, renderer);
-        let (_, event_statuses) = ui.update(
-            self.events.as_slice(),
-            cursor_position,
-            renderer,
-            clipboard,
-            &mut messages,
+    /// Rebuild the interfaces of [`IcedContext::display_retained`] calls with this message type
+    /// on their next display, in every window.
+    pub fn invalidate(&mut self) {
+        self.cache_map.invalidate::<M>();
+    }
+
+    /// Run the update phase of [`IcedContext::display`] in the primary window: input is
+    /// processed, messages are sent and captured input is recorded, but nothing is drawn.
+    ///
+    /// Draw the element later in the frame with [`IcedContext::draw`], usually from a system in
+    /// [`IcedSet::Draw`], so that game systems in between can handle the messages first.
+    /// ```ignore
+    /// app.add_system(ui_update.in_set(IcedSet::Display))
+    ///     .add_system(handle_messages.after(IcedSet::Display).before(IcedSet::Draw))
+    ///     .add_system(ui_draw.in_set(IcedSet::Draw));
+    /// ```
+    pub fn update<'a>(
+        &'a mut self,
+        element: impl Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    ) {
+        let Ok(window) = self.primary_window.get_single() else { return };
+        self.update_in(window, element);
+    }
+
+    /// Run the update phase of [`IcedContext::display_in`] in the given window or
+    /// [`IcedRenderTarget`] entity, without drawing anything.
+    pub fn update_in<'a>(
+        &'a mut self,
+        window: Entity,
+        element: impl Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    ) {
+        let translation = Vector::new(0.0, 0.0);
+        self.display_translated(
+            window,
+            || element,
+            translation,
+            None,
+            None,
+            Phase::Update,
+            None,
+        );
+    }
+
+    /// Draw an element that was updated earlier in the frame with [`IcedContext::update`], to
+    /// the primary window.
+    ///
+    /// The n-th draw call of a message type in a window uses the interface state of its n-th
+    /// update call, so the element should be built the same way, from state that may have
+    /// changed since.
+    pub fn draw<'a>(&'a mut self, element: impl Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>) {
+        let Ok(window) = self.primary_window.get_single() else { return };
+        self.draw_in(window, element);
+    }
+
+    /// Draw an element that was updated earlier in the frame with [`IcedContext::update_in`],
+    /// to the given window or [`IcedRenderTarget`] entity.
+    pub fn draw_in<'a>(
+        &'a mut self,
+        window: Entity,
+        element: impl Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    ) {
+        let translation = Vector::new(0.0, 0.0);
+        self.display_translated(
+            window,
+            || element,
+            translation,
+            None,
+            None,
+            Phase::Draw,
+            None,
+        );
+    }
+
+    /// Display an [`Element`] anchored to a position in the world, as seen by `camera`.
+    ///
+    /// The top-left corner of the element follows the projection of `world_position` in the
+    /// window the camera renders to. Nothing is displayed if the position is behind the camera
+    /// or the camera doesn't render to a window.
+    pub fn display_anchored<'a>(
+        &'a mut self,
+        world_position: Vec3,
+        camera: Entity,
+        element: impl Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    ) {
+        let Some((window, anchor)) = self.project(world_position, camera) else { return };
+        let translation = Vector::new(anchor.x, anchor.y);
+        self.display_translated(
+            window,
+            || element,
+            translation,
+            None,
+            None,
+            Phase::Both,
+            None,
+        );
+    }
+
+    /// Display many small elements in the primary window, like nameplates or health bars, each
+    /// with its top-left corner at a position in the UI.
+    ///
+    /// The elements are built, laid out and drawn as a single UI, which is much faster than a
+    /// [`display`](Self::display) call for each of them. Their state is kept by their index in
+    /// the batch, so the order should be stable, e.g. sorted by entity.
+    /// ```ignore
+    /// ctx.display_batch(units.iter().map(|(unit, position)| (position, health_bar(unit))));
+    /// ```
+    pub fn display_batch<'a, E>(&'a mut self, elements: impl IntoIterator<Item = (Point, E)>)
+    where
+        E: Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    {
+        let Ok(window) = self.primary_window.get_single() else { return };
+        self.display_batch_in(window, elements);
+    }
+
+    /// Display many small elements in a window or [`IcedRenderTarget`], like
+    /// [`IcedContext::display_batch`].
+    pub fn display_batch_in<'a, E>(
+        &'a mut self,
+        window: Entity,
+        elements: impl IntoIterator<Item = (Point, E)>,
+    ) where
+        E: Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    {
+        let translation = Vector::new(0.0, 0.0);
+        self.display_translated(
+            window,
+            || {
+                elements
+                    .into_iter()
+                    .map(|(position, element)| (position, element.into()))
+                    .collect::<batch::Batch<_, _>>()
+            },
+            translation,
+            None,
+            None,
+            Phase::Both,
+            None,
         );
+    }
+
+    /// Display many small elements anchored to positions in the world, as seen by `camera`, like
+    /// [`IcedContext::display_batch`].
+    ///
+    /// The top-left corner of each element follows the projection of its position, like with
+    /// [`IcedContext::display_anchored`], and elements behind the camera aren't displayed.
+    /// ```ignore
+    /// let nameplates = players
+    ///     .iter()
+    ///     .map(|(player, transform)| (transform.translation() + Vec3::Y * 2.0, nameplate(player)));
+    /// ctx.display_batch_anchored(camera, nameplates);
+    /// ```
+    pub fn display_batch_anchored<'a, E>(
+        &'a mut self,
+        camera: Entity,
+        elements: impl IntoIterator<Item = (Vec3, E)>,
+    ) where
+        E: Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    {
+        let primary_window = self.primary_window.get_single().ok();
+        let Some(window) = self
+            .cameras
+            .get(camera)
+            .ok()
+            .and_then(|(_, camera, _)| systems::camera_window(camera, primary_window))
+            else { return };
+        // Projected before displaying, since the elements borrow the context.
+        let elements: Vec<_> = elements
+            .into_iter()
+            .filter_map(|(world_position, element)| {
+                let (_, anchor) = self.project(world_position, camera)?;
+                Some((anchor, element))
+            })
+            .collect();
+        self.display_batch_in(window, elements);
+    }
 
-        messages.into_iter().for_each(|msg| self.messages.send(msg));
+    /// Display a tooltip above an entity in the world, e.g. while the cursor hovers it.
+    ///
+    /// Call this every frame that the tooltip is wanted. It's shown once it has been requested
+    /// for the show delay of the [`IcedTooltipSettings`], centered above the projection of the
+    /// entity, or below it when there's no room above, and kept within the UI.
+    /// ```ignore
+    /// if let Some(unit) = hovered.0 {
+    ///     ctx.tooltip_for(unit, container(text(&names.get(unit).unwrap().0)).padding(4));
+    /// }
+    /// ```
+    ///
+    /// There's one tooltip for each message type, so only the last of the requests for
+    /// different entities in a frame is kept.
+    pub fn tooltip_for<'a>(
+        &'a mut self,
+        entity: Entity,
+        element: impl Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    ) {
+        let settings = *self.tooltip_settings;
+        let now = self.time.elapsed();
+        let remaining = self
+            .cache_map
+            .tooltips
+            .entry(TypeId::of::<M>())
+            .or_default()
+            .request(entity, now, &settings);
+        if !remaining.is_zero() {
+            // The UI systems run again in time to show it, even while idle.
+            self.idle.request_redraw(Instant::now() + remaining);
+            return;
+        }
 
-        ui.draw(
-            renderer,
-            &self.settings.theme,
-            &self.settings.style,
-            cursor_position,
+        let primary_window = self.primary_window.get_single().ok();
+        let camera = settings.camera.or_else(|| {
+            self.cameras
+                .iter()
+                .filter(|(_, camera, _)| {
+                    camera.is_active && systems::camera_window(camera, primary_window).is_some()
+                })
+                .max_by_key(|(_, camera, _)| camera.order)
+                .map(|(camera, ..)| camera)
+        });
+        let Some(world_position) = self
+            .transforms
+            .get(entity)
+            .ok()
+            .map(|transform| transform.translation())
+            else { return };
+        let Some((window, anchor)) =
+            camera.and_then(|camera| self.project(world_position, camera)) else { return };
+        let tooltip = tooltip::Tooltip {
+            anchor,
+            gap: settings.gap,
+            margin: settings.margin,
+            content: element.into(),
+        };
+        let translation = Vector::new(0.0, 0.0);
+        self.display_translated(
+            window,
+            || tooltip,
+            translation,
+            None,
+            None,
+            Phase::Both,
+            None,
         );
+    }
+
+    /// Converts a position in the primary window, in logical pixels from its bottom-left corner
+    /// like `Window::cursor_position`, into the coordinates of the UIs displayed in it.
+    ///
+    /// This accounts for the scale factor of the UIs, and for the area they're laid out in with
+    /// the viewport, safe area and design resolution of the [`IcedSettings`], e.g. to place an
+    /// overlay next to a point picked in the window.
+    pub fn window_to_ui(&self, position: Vec2) -> Option<Point> {
+        let window = self.primary_window.get_single().ok()?;
+        self.window_to_ui_in(window, position)
+    }
+
+    /// Converts a position in a window into the coordinates of the UIs displayed in it, or in
+    /// an [`IcedCamera`] rendering to it, like [`IcedContext::window_to_ui`].
+    pub fn window_to_ui_in(&self, window: Entity, position: Vec2) -> Option<Point> {
+        let (window, area) = self.ui_area(window)?;
+        let bounds = self.viewports.get(&window)?.logical_size();
+        let bevy_window = self.windows.get(window).ok()?;
+        let position = Vec2::new(position.x, bevy_window.height() - position.y);
+        Some(systems::window_to_ui(bevy_window, bounds, position) - Vector::new(area.x, area.y))
+    }
+
+    /// Converts a point in the coordinates of the UIs of the primary window into a position in
+    /// the window, in logical pixels from its bottom-left corner. The inverse of
+    /// [`IcedContext::window_to_ui`].
+    pub fn ui_to_window(&self, point: Point) -> Option<Vec2> {
+        let window = self.primary_window.get_single().ok()?;
+        self.ui_to_window_in(window, point)
+    }
+
+    /// Converts a point in the coordinates of the UIs displayed in a window, or in an
+    /// [`IcedCamera`] rendering to it, into a position in the window, like
+    /// [`IcedContext::ui_to_window`].
+    pub fn ui_to_window_in(&self, window: Entity, point: Point) -> Option<Vec2> {
+        let (window, area) = self.ui_area(window)?;
+        let bounds = self.viewports.get(&window)?.logical_size();
+        let bevy_window = self.windows.get(window).ok()?;
+        let point = point + Vector::new(area.x, area.y);
+        let Vec2 { x, y } = systems::ui_to_window(bevy_window, bounds, point);
+        Some(Vec2::new(x, bevy_window.height() - y))
+    }
+
+    // The window a camera renders to, and its viewport in the UI space of the window.
+    fn camera_area(&self, camera: Entity) -> Option<(Entity, Rectangle)> {
+        let (_, camera, _) = self.cameras.get(camera).ok()?;
+        let window = systems::camera_window(camera, self.primary_window.get_single().ok())?;
+        let bounds = self.viewports.get(&window)?.logical_size();
+        let area = systems::camera_area(camera, self.windows.get(window).ok()?, bounds)?;
+        Some((window, area))
+    }
+
+    // Projects a world position through a camera into the coordinates of the UIs of the
+    // camera's window, which are laid out in its UI area.
+    fn project(&self, world_position: Vec3, camera: Entity) -> Option<(Entity, Point)> {
+        let (_, camera, camera_transform) = self.cameras.get(camera).ok()?;
+        let primary_window = self.primary_window.get_single().ok();
+        let Some(NormalizedRenderTarget::Window(window_ref)) =
+            camera.target.normalize(primary_window) else { return None };
+        let window = window_ref.entity();
+        let bounds = self.viewports.get(&window)?.logical_size();
+        let bevy_window = self.windows.get(window).ok()?;
+
+        let position = camera.world_to_viewport(camera_transform, world_position)?;
+        let viewport_size = camera.logical_viewport_size()?;
+        let (viewport_min, _) = camera.logical_viewport_rect().unwrap_or_default();
+        let position = Vec2::new(
+            viewport_min.x + position.x,
+            viewport_min.y + viewport_size.y - position.y,
+        );
+
+        let (_, area) = self.ui_area(window)?;
+        let point = systems::window_to_ui(bevy_window, bounds, position);
+        Some((window, point - Vector::new(area.x, area.y)))
+    }
+
+    // The window that the UIs of a window, render target or camera are displayed in, and the
+    // area they're laid out in, in the UI space of the window.
+    fn ui_area(&self, target: Entity) -> Option<(Entity, Rectangle)> {
+        // UIs displayed in a camera are laid out in its viewport, in the window it renders to.
+        if self.iced_cameras.contains(target) {
+            return self.camera_area(target);
+        }
+        let viewport = self.viewports.get(&target)?;
+        let window_bounds = viewport.logical_size();
+        let bevy_window = match self.windows.get(target) {
+            Ok(bevy_window) => bevy_window,
+            // The UIs of render targets fill their images.
+            Err(_) => return Some((target, Rectangle::with_size(window_bounds))),
+        };
+        // Settings on the window override the global ones.
+        let window_settings = self.window_settings.get(target).ok();
+        let settings = window_settings.as_deref().unwrap_or(&self.settings);
+        let area = settings
+            .viewport_rect
+            .map_or(Rectangle::with_size(window_bounds), |rect| {
+                rect.to_ui(bevy_window, viewport.scale_factor())
+            });
+        let area = self.safe_area.shrink(area, bevy_window, window_bounds);
+        let area = match settings.design_size() {
+            Some(size) => Rectangle::new(
+                Point::new(
+                    area.x + (area.width - size.width) / 2.0,
+                    area.y + (area.height - size.height) / 2.0,
+                ),
+                size,
+            ),
+            None => area,
+        };
+        Some((target, area))
+    }
+
+    // Displays the element returned by `view`, unless the UI is unchanged since the last frame.
+    //
+    // With a `key`, the output of the last frame is drawn again without calling `view` while
+    // the key, the cursor and the viewport are the same, and there are no events or messages
+    // for the UI.
+    #[allow(clippy::too_many_arguments)]
+    fn display_translated<'a, E>(
+        &'a mut self,
+        window: Entity,
+        view: impl FnOnce() -> E,
+        translation: Vector,
+        theme: Option<(&Theme, &iced_native::renderer::Style)>,
+        owner: Option<Entity>,
+        phase: Phase,
+        key: Option<u64>,
+    ) where
+        E: Into<Element<'a, M, iced_wgpu::Renderer<Theme>>>,
+    {
+        // The interface is built from the element every frame, and only its state is kept.
+        let build = |entry: &mut CacheEntry,
+                     bounds: iced_native::Size,
+                     _: bool,
+                     renderer: &mut iced_wgpu::Renderer<Theme>| {
+            let cache = entry.cache.take().unwrap_or_default();
+            UserInterface::build(view(), bounds, cache, renderer)
+        };
+        let keep = |entry: &mut CacheEntry,
+                    ui: UserInterface<'a, M, iced_wgpu::Renderer<Theme>>,
+                    _: iced_native::Size,
+                    _: bool| {
+            entry.cache = Some(ui.into_cache());
+        };
+        self.display_interface(window, build, keep, translation, theme, owner, phase, key);
+    }
+
+    // Displays the interface returned by `build`, which is given the cache entry of the call,
+    // the bounds of the UI, whether the settings changed and the renderer. `keep` stores the
+    // interface in the entry afterwards, with its bounds and whether it's outdated.
+    #[allow(clippy::too_many_arguments)]
+    fn display_interface<'b>(
+        &mut self,
+        window: Entity,
+        build: impl FnOnce(
+            &mut CacheEntry,
+            iced_native::Size,
+            bool,
+            &mut iced_wgpu::Renderer<Theme>,
+        ) -> UserInterface<'b, M, iced_wgpu::Renderer<Theme>>,
+        keep: impl FnOnce(
+            &mut CacheEntry,
+            UserInterface<'b, M, iced_wgpu::Renderer<Theme>>,
+            iced_native::Size,
+            bool,
+        ),
+        translation: Vector,
+        theme: Option<(&Theme, &iced_native::renderer::Style)>,
+        owner: Option<Entity>,
+        phase: Phase,
+        key: Option<u64>,
+    ) {
+        self.result.begin_display();
+        // Without a window or a render device, e.g. on a dedicated server, nothing is displayed.
+        let target = window;
+        let Some((window, area)) = self.ui_area(target) else { return };
+        let Some(viewport) = self.viewports.get(&window) else { return };
+        if self.renderer.is_none() {
+            return;
+        }
+        let window_bounds = viewport.logical_size();
+        let scale_factor = viewport.scale_factor();
+        // Settings on the window override the global ones.
+        let window_settings = self.window_settings.get(window).ok();
+        let settings_changed = self.settings.is_changed()
+            || window_settings
+                .as_ref()
+                .map_or(false, |settings| settings.is_changed());
+        let settings = window_settings.as_deref().unwrap_or(&self.settings);
+        let bounds = area.size();
+        let translation = translation + Vector::new(area.x, area.y);
+        let _span = info_span!("iced_display", message = std::any::type_name::<M>()).entered();
+
+        // UIs below a modal one only receive window events, and nothing in them is hovered.
+        let z = self.layer_order.get::<M>();
+        let modal = self.modal_layer.z.map_or(false, |modal_z| z >= modal_z);
+        let blocked = !modal && self.modal_layer.z.is_some() && self.cache_map.modal_shown(window);
+
+        let virtual_cursor_position = process_virtual_cursor_input(self, window, window_bounds);
+        let grabbed = self
+            .windows
+            .get(window)
+            .map_or(false, systems::cursor_grabbed);
+        let cursor_position = if blocked || (grabbed && virtual_cursor_position.is_none()) {
+            // Like Iced does for a cursor outside of the window, so that nothing is hovered.
+            iced_native::Point::new(-1.0, -1.0)
+        } else {
+            virtual_cursor_position
+                .or_else(|| self.synthetic.cursors.get(&window).copied())
+                .or_else(|| {
+                    let bevy_window = self.windows.get(window).ok()?;
+                    let Vec2 { x, y } = bevy_window.cursor_position()?;
+                    let position = Vec2::new(x, bevy_window.height() - y);
+                    Some(systems::window_to_ui(bevy_window, window_bounds, position))
+                })
+                .or_else(|| process_touch_input(self, window, window_bounds))
+                .unwrap_or(iced_native::Point::ORIGIN)
+                - translation
+        };
+
+        let forwarding = self.input_config.for_message::<M>();
+        // The buffers are given back to the cache at the end, so they're allocated only once.
+        let mut events = std::mem::take(&mut self.cache_map.events);
+        events.clear();
+        let mut messages = self.cache_map.commands::<M>().take_messages();
+        let mut operations = Vec::new();
+        if phase != Phase::Draw {
+            events.extend(
+                self.events
+                    .get(target)
+                    .iter()
+                    .filter(|event| forwarding.forwards(event))
+                    .filter(|event| !blocked || matches!(event, iced::Event::Window(_)))
+                    .map(|event| translate_event(event.clone(), translation)),
+            );
+            let commands = self.cache_map.commands::<M>();
+            messages.extend(commands.finished());
+            operations = commands.take_operations();
+        }
+
+        let renderer = &mut self.renderer.as_mut().unwrap().renderer;
+        let entry = self
+            .cache_map
+            .entry::<M>(target, owner, phase == Phase::Draw);
+        let unchanged = phase == Phase::Both
+            && key.is_some()
+            && events.iter().all(idle::is_redraw)
+            && messages.is_empty()
+            && operations.is_empty()
+            && !settings_changed;
+        let reuse = entry.output.as_ref().map_or(false, |output| {
+            unchanged
+                && output.key == key
+                && output.cursor_position == cursor_position
+                && output.bounds == bounds
+                && output.scale_factor == scale_factor
+                && output.redraw_at.map_or(true, |at| at > Instant::now())
+        });
+
+        let mut redraw_at = None;
+        let mut outdated = false;
+        // A UI drawn after its update phase keeps the focus found by the update.
+        let mut wants_keyboard_input = phase == Phase::Draw && self.result.wants_keyboard_input;
+        let output = if reuse {
+            entry.output.clone().unwrap()
+        } else {
+            let start = Instant::now();
+            self.debug.debug.layout_started();
+            let mut ui = build(entry, bounds, settings_changed, renderer);
+            self.debug.debug.layout_finished();
+            self.timings.layout += start.elapsed();
+
+            if phase != Phase::Draw {
+                let start = Instant::now();
+                self.debug.debug.event_processing_started();
+                for operation in operations {
+                    command::run_operation(&mut ui, renderer, operation, &mut messages);
+                }
+                let (state, event_statuses) = ui.update(
+                    &events,
+                    cursor_position,
+                    renderer,
+                    self.clipboard.0.get().as_mut(),
+                    &mut messages,
+                );
+
+                // Messages usually change the state that the interface shows.
+                outdated = matches!(state, user_interface::State::Outdated) || !messages.is_empty();
+                messages.drain(..).for_each(|msg| self.messages.send(msg));
+                if let user_interface::State::Updated {
+                    redraw_request: Some(request),
+                } = state
+                {
+                    let at = idle::redraw_time(request);
+                    self.idle.request_redraw(at);
+                    redraw_at = Some(at);
+                }
+
+                let mut focus_probe = FocusProbe::default();
+                ui.operate(renderer, &mut focus_probe);
+                wants_keyboard_input = focus_probe.focused;
+                self.result.wants_keyboard_input |= wants_keyboard_input;
+                self.debug.debug.event_processing_finished();
+                self.timings.update += start.elapsed();
+
+                for (event, status) in events.drain(..).zip(event_statuses) {
+                    if status == Status::Captured {
+                        self.captured.0.push(event.clone());
+                        self.result.captured_events.push(event);
+                    }
+                }
+                // Events that weren't forwarded are left for other UIs in the same window, and
+                // all of them for the modal UI above a blocked one.
+                if !blocked {
+                    self.events
+                        .retain_window(target, |event| !forwarding.forwards(event));
+                }
+            }
+
+            if phase == Phase::Update {
+                keep(entry, ui, bounds, outdated);
+                self.cache_map.events = events;
+                self.cache_map.commands::<M>().reuse_messages(messages);
+                return;
+            }
+
+            let (theme, style) = theme.unwrap_or((&settings.theme, &settings.style));
+            let start = Instant::now();
+            self.debug.debug.draw_started();
+            let interaction = ui.draw(renderer, theme, style, cursor_position);
+            self.debug.debug.draw_finished();
+            self.timings.draw += start.elapsed();
+            keep(entry, ui, bounds, outdated);
+
+            let mut occluded = Vec::new();
+            let wants_pointer_input = renderer.with_primitives(|_, primitives| {
+                for primitive in primitives {
+                    occlusion_rects(primitive, translation, None, &mut occluded);
+                }
+                primitives
+                    .iter()
+                    .any(|primitive| hit_test(primitive, cursor_position))
+            });
+            #[cfg(feature = "gamepad")]
+            if let Some(position) = virtual_cursor_position {
+                self.virtual_cursor.draw(renderer, position - translation);
+            }
 
-        self.result.captured_events = self.events.iter()
-            .zip(event_statuses)
-            .filter_map(|(ev, status)|
-                if status == Status::Captured { Some(ev.clone()) } else { None })
-            .collect::<Vec<_>>();
-        self.events.clear();
-        *cache_entry = Some(ui.into_cache());
-        self.did_draw
-            .store(true, std::sync::atomic::Ordering::Relaxed);
-
-        let mut wants_pointer_input = false;
-        renderer.with_primitives(|_, primitives| primitives.iter().for_each(|primitive| {
-            if !wants_pointer_input && hit_test(primitive, cursor_position) {
-                wants_pointer_input = true;
+            let mut primitives = renderer.with_primitives(|_, primitives| primitives.to_vec());
+            if translation != Vector::new(0.0, 0.0) {
+                primitives = vec![Primitive::Translate {
+                    translation,
+                    content: Box::new(Primitive::Group { primitives }),
+                }];
             }
-        }));
-        self.result.wants_pointer_input = wants_pointer_input;
+            // Each UI is clipped to the viewport, which draws it in a separate Iced layer on top
+            // of the ones before it.
+            let primitives = vec![Primitive::Clip {
+                bounds: area,
+                content: Box::new(Primitive::Group { primitives }),
+            }];
+            let output = DisplayOutput {
+                key,
+                cursor_position,
+                bounds,
+                scale_factor,
+                primitives,
+                occluded,
+                interaction,
+                wants_pointer_input,
+                wants_keyboard_input,
+                redraw_at,
+            };
+            entry.output = key.map(|_| output.clone());
+            output
+        };
+        self.cache_map.events = events;
+        self.cache_map.commands::<M>().reuse_messages(messages);
+
+        if modal {
+            self.push_scrim(window, window_bounds);
+        }
+        // The scrim of a modal UI covers the whole window.
+        self.result.wants_pointer_input |= output.wants_pointer_input || modal;
+        self.result.wants_keyboard_input |= output.wants_keyboard_input;
+        // Like the cursor icons, the interaction of a window is the first one that isn't idle.
+        let interaction = self.result.interactions.entry(window).or_default();
+        if *interaction == iced_native::mouse::Interaction::Idle {
+            *interaction = output.interaction;
+        }
+        if self.windows.contains(window) {
+            self.interactions.insert(window, output.interaction);
+        }
+        self.occlusion
+            .pending
+            .entry(window)
+            .or_default()
+            .extend(output.occluded);
+        self.frames.entry(window).or_default().push(render::Layer {
+            z,
+            primitives: output.primitives,
+        });
+    }
+
+    // Draws the scrim of the modal layer under the first modal UI of the frame in the window.
+    fn push_scrim(&mut self, window: Entity, bounds: iced_native::Size) {
+        let Some(z) = self.modal_layer.z else { return };
+        let frame = self.cache_map.frame;
+        if self.cache_map.modals.insert(window, frame) == Some(frame) {
+            return;
+        }
+        self.frames.entry(window).or_default().push(render::Layer {
+            z,
+            primitives: vec![Primitive::Quad {
+                bounds: Rectangle::with_size(bounds),
+                background: Background::Color(self.modal_layer.scrim),
+                border_radius: [0.0; 4],
+                border_width: 0.0,
+                border_color: iced::Color::TRANSPARENT,
+            }],
+        });
+    }
+}
+
+// What a display call drew, kept to draw it again while nothing it depends on changes.
+#[derive(Clone)]
+struct DisplayOutput {
+    key: Option<u64>,
+    cursor_position: Point,
+    bounds: iced_native::Size,
+    // Redrawn when the window moves to a monitor with another scale factor, even if its logical
+    // size stays the same.
+    scale_factor: f64,
+    primitives: Vec<Primitive>,
+    occluded: Vec<Rectangle>,
+    interaction: iced_native::mouse::Interaction,
+    wants_pointer_input: bool,
+    wants_keyboard_input: bool,
+    // When a widget, like a blinking text input, wants to be drawn again.
+    redraw_at: Option<Instant>,
+}
+
+fn not_idle(idle: Res<IcedIdle>) -> bool {
+    !idle.is_idle()
+}
+
+// The parts of a display call to run.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    // Process input and send messages.
+    Update,
+    // Draw the interface as updated earlier in the frame.
+    Draw,
+    Both,
+}
+
+// An operation that checks whether any focusable widget has keyboard focus.
+#[derive(Default)]
+struct FocusProbe {
+    focused: bool,
+}
+
+impl<T> Operation<T> for FocusProbe {
+    fn container(
+        &mut self,
+        _id: Option<&widget::Id>,
+        operate_on_children: &mut dyn FnMut(&mut dyn Operation<T>),
+    ) {
+        operate_on_children(self)
+    }
+
+    fn focusable(&mut self, state: &mut dyn Focusable, _id: Option<&widget::Id>) {
+        self.focused |= state.is_focused();
     }
 }
 
@@ -322,15 +2884,139 @@ fn hit_test(primitive: &Primitive, cursor_position: Point) -> bool {
         Primitive::Clip { bounds, content } =>
             bounds.contains(cursor_position) && hit_test(content, cursor_position),
         Primitive::Translate { translation, content } =>
-            hit_test(content, cursor_position + *translation),
+            hit_test(content, cursor_position - *translation),
         Primitive::Svg { bounds, .. } => bounds.contains(cursor_position),
+        Primitive::SolidMesh { size, .. } | Primitive::GradientMesh { size, .. } =>
+            Rectangle::with_size(*size).contains(cursor_position),
+        Primitive::Cache { content } => hit_test(content, cursor_position),
         _ => false
     }
 }
 
+// Moves the position of a mouse or touch event, given in the space of the window UIs, into the
+// space of a UI drawn at `translation`, like its cursor position.
+fn translate_event(event: iced::Event, translation: Vector) -> iced::Event {
+    use iced_native::{mouse, touch};
+
+    match event {
+        iced::Event::Mouse(mouse::Event::CursorMoved { position }) => {
+            iced::Event::Mouse(mouse::Event::CursorMoved {
+                position: position - translation,
+            })
+        }
+        iced::Event::Touch(event) => iced::Event::Touch(match event {
+            touch::Event::FingerPressed { id, position } => touch::Event::FingerPressed {
+                id,
+                position: position - translation,
+            },
+            touch::Event::FingerMoved { id, position } => touch::Event::FingerMoved {
+                id,
+                position: position - translation,
+            },
+            touch::Event::FingerLifted { id, position } => touch::Event::FingerLifted {
+                id,
+                position: position - translation,
+            },
+            touch::Event::FingerLost { id, position } => touch::Event::FingerLost {
+                id,
+                position: position - translation,
+            },
+        }),
+        event => event,
+    }
+}
+
+// Collects the bounds of opaque quads and images, in the coordinates of the UI.
+fn occlusion_rects(
+    primitive: &Primitive,
+    translation: Vector,
+    clip: Option<Rectangle>,
+    rects: &mut Vec<Rectangle>,
+) {
+    match primitive {
+        Primitive::Quad {
+            bounds, background, ..
+        } if matches!(background, Background::Color(color) if color.a >= 1.0) => {
+            push_occlusion_rect(bounds, translation, clip, rects)
+        }
+        Primitive::Image { bounds, .. } => push_occlusion_rect(bounds, translation, clip, rects),
+        Primitive::Group { primitives } => primitives
+            .iter()
+            .for_each(|p| occlusion_rects(p, translation, clip, rects)),
+        Primitive::Clip { bounds, content } => {
+            let bounds = Rectangle {
+                x: bounds.x + translation.x,
+                y: bounds.y + translation.y,
+                ..*bounds
+            };
+            let clip = match clip {
+                Some(clip) => bounds.intersection(&clip),
+                None => Some(bounds),
+            };
+            if clip.is_some() {
+                occlusion_rects(content, translation, clip, rects);
+            }
+        }
+        Primitive::Translate {
+            translation: offset,
+            content,
+        } => occlusion_rects(content, translation + *offset, clip, rects),
+        _ => {}
+    }
+}
+
+fn push_occlusion_rect(
+    bounds: &Rectangle,
+    translation: Vector,
+    clip: Option<Rectangle>,
+    rects: &mut Vec<Rectangle>,
+) {
+    let bounds = Rectangle {
+        x: bounds.x + translation.x,
+        y: bounds.y + translation.y,
+        ..*bounds
+    };
+    let Some(bounds) = clip.map_or(Some(bounds), |clip| bounds.intersection(&clip)) else { return };
+
+    // Skip areas that are already covered.
+    let bottom_right = Point::new(bounds.x + bounds.width, bounds.y + bounds.height);
+    if !rects
+        .iter()
+        .any(|rect| rect.contains(bounds.position()) && rect.contains(bottom_right))
+    {
+        rects.push(bounds);
+    }
+}
+
+#[cfg(feature = "gamepad")]
+fn process_virtual_cursor_input<M: Event, Theme: Send + Sync + 'static>(
+    context: &IcedContext<M, Theme>,
+    window: Entity,
+    bounds: iced_native::Size,
+) -> Option<iced_native::Point> {
+    let bevy_window = context.windows.get(window).ok()?;
+    context
+        .virtual_cursor
+        .ui_position(window, bevy_window, bounds)
+}
+
+#[cfg(not(feature = "gamepad"))]
+fn process_virtual_cursor_input<M: Event, Theme: Send + Sync + 'static>(
+    _: &IcedContext<M, Theme>,
+    _: Entity,
+    _: iced_native::Size,
+) -> Option<iced_native::Point> {
+    None
+}
+
 #[cfg(feature = "touch")]
 /// To correctly process input as last resort events are used
-fn process_touch_input<M: Event>(context: &IcedContext<M>) -> Option<iced_native::Point> {
+fn process_touch_input<M: Event, Theme: Send + Sync + 'static>(
+    context: &IcedContext<M, Theme>,
+    window: Entity,
+    bounds: iced_native::Size,
+) -> Option<iced_native::Point> {
+    let bevy_window = context.windows.get(window).ok()?;
     context
         .touches
         .first_pressed_position()
@@ -339,9 +3025,10 @@ fn process_touch_input<M: Event>(context: &IcedContext<M>) -> Option<iced_native
             .iter_just_released()
             .map(|touch| touch.position())
             .next())
-        .map(|Vec2 { x, y }| iced_native::Point { x, y })
+        .map(|position| systems::window_to_ui(bevy_window, bounds, position))
         .or(context
             .events
+            .get(window)
             .iter()
             .filter_map(|ev| {
                 if let iced_native::Event::Touch(
@@ -361,6 +3048,10 @@ fn process_touch_input<M: Event>(context: &IcedContext<M>) -> Option<iced_native
 }
 
 #[cfg(not(feature = "touch"))]
-fn process_touch_input<M: Event>(_: &IcedContext<M>) -> Option<iced_native::Point> {
+fn process_touch_input<M: Event, Theme: Send + Sync + 'static>(
+    _: &IcedContext<M, Theme>,
+    _: Entity,
+    _: iced_native::Size,
+) -> Option<iced_native::Point> {
     None
 }
@@ -0,0 +1,68 @@
+use bevy_app::{App, Plugin};
+use bevy_ecs::prelude::{EventWriter, Query, Res};
+use bevy_ecs::schedule::IntoSystemConfig;
+use bevy_picking_core::backend::{HitData, PointerHits};
+use bevy_picking_core::pointer::{PointerId, PointerLocation};
+use bevy_picking_core::PickSet;
+use bevy_render::camera::NormalizedRenderTarget;
+use bevy_window::Window;
+
+use crate::render::{IcedFrames, ViewportResource};
+use crate::systems::{cursor_grabbed, hit_test_previous_frame, hit_test_window};
+
+/// A `bevy_mod_picking` backend that stops pointers over the UIs from picking the entities
+/// behind them, e.g. 3D objects under a UI panel.
+///
+/// Requires the `picking` feature. Add it after [`IcedPlugin`](crate::IcedPlugin) and the
+/// picking plugins:
+/// ```ignore
+/// app.add_plugin(IcedPlugin::default())
+///     .add_plugins(DefaultPickingPlugins)
+///     .add_plugin(IcedPickingPlugin);
+/// ```
+#[derive(Default)]
+pub struct IcedPickingPlugin;
+
+impl IcedPickingPlugin {
+    // Above every other backend, so the window entity blocks the entities hit by them.
+    const ORDER: f32 = 1_000_000.0;
+
+    // Reports the window as hit by the pointers over what its UIs drew during the previous
+    // frame.
+    fn block_picking(
+        frames: Res<IcedFrames>,
+        viewports: Res<ViewportResource>,
+        windows: Query<&Window>,
+        pointers: Query<(&PointerId, &PointerLocation)>,
+        mut hits: EventWriter<PointerHits>,
+    ) {
+        for (pointer, location) in pointers.iter() {
+            let Some(location) = location.location() else { continue };
+            let NormalizedRenderTarget::Window(window) = &location.target else { continue };
+            let entity = window.entity();
+            let Ok(window) = windows.get(entity) else { continue };
+            // The UIs don't use a grabbed cursor, so they don't block it either.
+            if *pointer == PointerId::Mouse && cursor_grabbed(window) {
+                continue;
+            }
+            if !hit_test_window(&frames, &viewports, entity, window, location.position) {
+                continue;
+            }
+            hits.send(PointerHits::new(
+                *pointer,
+                vec![(entity, HitData::new(entity, 0.0, None, None))],
+                Self::ORDER,
+            ));
+        }
+    }
+}
+
+impl Plugin for IcedPickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(
+            Self::block_picking
+                .after(hit_test_previous_frame)
+                .in_set(PickSet::Backend),
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use bevy_ecs::event::{Event, Events, ManualEventReader};
+use bevy_ecs::prelude::{Local, World};
+use bevy_ecs::system::{Resource, SystemState};
+use bevy_ecs::world::Mut;
+use iced_native::{Command, Element};
+
+use crate::IcedContext;
+
+/// A retained UI in the style of an Iced application, with its own state.
+///
+/// Register it with [`IcedAppExt::add_iced_program`](crate::IcedAppExt::add_iced_program).
+/// Every frame, the messages sent by the UI during the previous frame are passed to
+/// [`IcedProgram::update`], and the element returned by [`IcedProgram::view`] is displayed in
+/// the primary window.
+pub trait IcedProgram: Send + Sync + 'static {
+    /// The messages produced by the UI. They are also sent as events, so other systems can
+    /// react to them.
+    type Message: Event + Clone;
+
+    /// Updates the state of the program, with full access to the world.
+    fn update(&mut self, message: Self::Message, world: &mut World) -> Command<Self::Message>;
+
+    /// Builds the UI from the state of the program and the world.
+    fn view(&self, world: &World) -> Element<'_, Self::Message, iced_wgpu::Renderer>;
+}
+
+// The state of a registered program.
+#[derive(Resource)]
+pub(crate) struct ProgramState<P>(pub(crate) P);
+
+pub(crate) fn run_program<P: IcedProgram>(
+    world: &mut World,
+    context: &mut SystemState<IcedContext<P::Message>>,
+    mut reader: Local<ManualEventReader<P::Message>>,
+) {
+    let events = world.resource::<Events<P::Message>>();
+    let messages: Vec<_> = reader.iter(events).cloned().collect();
+
+    world.resource_scope(|world, mut program: Mut<ProgramState<P>>| {
+        let commands: Vec<_> = messages
+            .into_iter()
+            .map(|message| program.0.update(message, world))
+            .collect();
+
+        let element = program.0.view(world);
+        let mut ctx = context.get_mut(world);
+        for command in commands {
+            ctx.perform(command);
+        }
+        ctx.display(element);
+    });
+}
@@ -0,0 +1,455 @@
+use std::fmt::Write as _;
+use std::path::Path;
+use std::str::FromStr;
+use std::{fs, io};
+
+use bevy_ecs::prelude::{Entity, Query};
+use bevy_ecs::system::{Res, ResMut, Resource};
+use bevy_input::keyboard::KeyCode as BevyKeyCode;
+use bevy_math::Vec2;
+use bevy_reflect::{DynamicEnum, DynamicVariant, FromReflect, TypeInfo, Typed};
+use bevy_utils::HashMap;
+use bevy_window::Window;
+use iced_native::keyboard::{self, KeyCode, Modifiers};
+use iced_native::touch::{self, Finger};
+use iced_native::{mouse, window, Event as IcedEvent, Point};
+
+use crate::conversions;
+use crate::render::ViewportResource;
+use crate::systems::{window_to_ui, IcedEventQueue, IcedSyntheticInput};
+
+const HEADER: &str = "bevy_iced input recording 1";
+
+// The input received by the UIs during one frame.
+#[derive(Default, Debug, PartialEq)]
+struct RecordedFrame {
+    cursors: Vec<(Entity, Point)>,
+    events: Vec<(Entity, IcedEvent)>,
+}
+
+/// Records the input received by the UIs every frame, to reproduce a session with
+/// [`IcedInputReplay`].
+///
+/// Insert it to start recording, and save it when done, e.g. when the app exits:
+/// ```ignore
+/// app.insert_resource(IcedInputRecorder::default())
+///     .add_system(save_recording.in_base_set(CoreSet::Last));
+///
+/// fn save_recording(mut exit: EventReader<AppExit>, recorder: Res<IcedInputRecorder>) {
+///     if exit.iter().next().is_some() {
+///         recorder.save("input.txt").unwrap();
+///     }
+/// }
+/// ```
+///
+/// The mouse, keyboard, touch and focus events of each window and render target are recorded,
+/// along with the position of the cursor. Windows are identified by their entity, so the app
+/// has to spawn them in the same order when the recording is replayed.
+#[derive(Resource, Default)]
+pub struct IcedInputRecorder {
+    frames: Vec<RecordedFrame>,
+}
+
+impl IcedInputRecorder {
+    /// The number of recorded frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Whether no frame was recorded yet.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Writes the recording to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    /// The recording in the text format read by [`IcedInputReplay::parse`].
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{HEADER}\nframes {}\n", self.frames.len());
+        for (index, frame) in self.frames.iter().enumerate() {
+            for (window, position) in &frame.cursors {
+                let (x, y) = (position.x, position.y);
+                let _ = writeln!(text, "{index} cursor {} {x} {y}", window.to_bits());
+            }
+            for (window, event) in &frame.events {
+                let Some(event) = encode(event) else { continue };
+                let _ = writeln!(text, "{index} event {} {event}", window.to_bits());
+            }
+        }
+        text
+    }
+}
+
+/// Feeds the input of an [`IcedInputRecorder`] back to the UIs, one recorded frame per frame.
+///
+/// While it runs, the recorded events replace the mouse, keyboard and touch events received by
+/// the app, and the recorded cursor positions replace those of the windows.
+/// ```ignore
+/// app.insert_resource(IcedInputReplay::load("input.txt")?);
+/// ```
+#[derive(Resource)]
+pub struct IcedInputReplay {
+    // The frames with input by index, as the frames without any aren't written.
+    frames: HashMap<usize, RecordedFrame>,
+    len: usize,
+    next: usize,
+}
+
+impl IcedInputReplay {
+    /// Reads a recording saved with [`IcedInputRecorder::save`].
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Reads a recording in the format of [`IcedInputRecorder::to_text`].
+    pub fn parse(text: &str) -> io::Result<Self> {
+        let invalid = |line: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid input recording line: {line}"),
+            )
+        };
+        let mut lines = text.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(invalid("missing header"));
+        }
+        let len = lines
+            .next()
+            .and_then(|line| line.strip_prefix("frames "))
+            .and_then(|count| count.parse().ok())
+            .ok_or_else(|| invalid("missing frame count"))?;
+
+        // The frames are only created for the lines read, as the count comes from the file.
+        let mut frames = HashMap::<usize, RecordedFrame>::default();
+        let key_codes = key_codes();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let tokens: Vec<_> = line.split_whitespace().collect();
+            let (Some(frame), Some(window)) = (
+                number::<usize>(&tokens, 0)
+                    .filter(|i| *i < len)
+                    .map(|i| frames.entry(i).or_default()),
+                number(&tokens, 2).map(Entity::from_bits),
+            ) else {
+                return Err(invalid(line));
+            };
+            match tokens.get(1).copied() {
+                Some("cursor") => {
+                    let position = point(&tokens, 3).ok_or_else(|| invalid(line))?;
+                    frame.cursors.push((window, position));
+                }
+                Some("event") => {
+                    let event = decode(&tokens[3..], &key_codes).ok_or_else(|| invalid(line))?;
+                    frame.events.push((window, event));
+                }
+                _ => return Err(invalid(line)),
+            }
+        }
+        Ok(Self {
+            frames,
+            len,
+            next: 0,
+        })
+    }
+
+    /// Whether all the recorded frames were replayed.
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.len
+    }
+}
+
+/// Records the input of this frame, including injected events.
+pub(crate) fn record_input(
+    recorder: Option<ResMut<IcedInputRecorder>>,
+    event_queue: Res<IcedEventQueue>,
+    synthetic: Res<IcedSyntheticInput>,
+    windows: Query<(Entity, &Window)>,
+    viewports: Res<ViewportResource>,
+) {
+    let Some(mut recorder) = recorder else { return };
+    let cursors = windows
+        .iter()
+        .filter_map(|(entity, window)| {
+            if let Some(position) = synthetic.cursors.get(&entity) {
+                return Some((entity, *position));
+            }
+            let viewport = viewports.get(&entity)?;
+            let Vec2 { x, y } = window.cursor_position()?;
+            let position = Vec2::new(x, window.height() - y);
+            Some((
+                entity,
+                window_to_ui(window, viewport.logical_size(), position),
+            ))
+        })
+        .collect();
+    let events = event_queue
+        .windows()
+        .flat_map(|(window, events)| {
+            events
+                .iter()
+                .filter(|event| encode(event).is_some())
+                .map(move |event| (window, event.clone()))
+        })
+        .collect();
+    recorder.frames.push(RecordedFrame { cursors, events });
+}
+
+/// Replaces the input of this frame with the next recorded frame.
+pub(crate) fn replay_input(
+    replay: Option<ResMut<IcedInputReplay>>,
+    mut event_queue: ResMut<IcedEventQueue>,
+    mut synthetic: ResMut<IcedSyntheticInput>,
+) {
+    let Some(mut replay) = replay else { return };
+    if replay.is_finished() {
+        return;
+    }
+    let index = replay.next;
+    replay.next += 1;
+
+    event_queue.retain(|event| encode(event).is_none());
+    let Some(frame) = replay.frames.get(&index) else {
+        synthetic.cursors.clear();
+        return;
+    };
+    for (window, event) in &frame.events {
+        event_queue.push(*window, event.clone());
+    }
+    synthetic.cursors = frame.cursors.iter().copied().collect();
+}
+
+// Describes an input event on one line, or returns `None` for other events.
+fn encode(event: &IcedEvent) -> Option<String> {
+    let button = |button: &mouse::Button| match button {
+        mouse::Button::Left => "left".to_string(),
+        mouse::Button::Right => "right".to_string(),
+        mouse::Button::Middle => "middle".to_string(),
+        mouse::Button::Other(other) => other.to_string(),
+    };
+    let text = match event {
+        IcedEvent::Mouse(event) => match event {
+            mouse::Event::CursorEntered => "cursor_entered".to_string(),
+            mouse::Event::CursorLeft => "cursor_left".to_string(),
+            mouse::Event::CursorMoved { position } => {
+                format!("cursor_moved {} {}", position.x, position.y)
+            }
+            mouse::Event::ButtonPressed(b) => format!("button_pressed {}", button(b)),
+            mouse::Event::ButtonReleased(b) => format!("button_released {}", button(b)),
+            mouse::Event::WheelScrolled {
+                delta: mouse::ScrollDelta::Lines { x, y },
+            } => format!("wheel_lines {x} {y}"),
+            mouse::Event::WheelScrolled {
+                delta: mouse::ScrollDelta::Pixels { x, y },
+            } => format!("wheel_pixels {x} {y}"),
+        },
+        IcedEvent::Keyboard(event) => match event {
+            keyboard::Event::KeyPressed {
+                key_code,
+                modifiers,
+            } => format!("key_pressed {key_code:?} {}", modifiers.bits()),
+            keyboard::Event::KeyReleased {
+                key_code,
+                modifiers,
+            } => format!("key_released {key_code:?} {}", modifiers.bits()),
+            keyboard::Event::CharacterReceived(c) => format!("character {}", *c as u32),
+            keyboard::Event::ModifiersChanged(modifiers) => {
+                format!("modifiers {}", modifiers.bits())
+            }
+        },
+        IcedEvent::Touch(event) => {
+            let (name, id, position) = match event {
+                touch::Event::FingerPressed { id, position } => ("finger_pressed", id, position),
+                touch::Event::FingerMoved { id, position } => ("finger_moved", id, position),
+                touch::Event::FingerLifted { id, position } => ("finger_lifted", id, position),
+                touch::Event::FingerLost { id, position } => ("finger_lost", id, position),
+            };
+            format!("{name} {} {} {}", id.0, position.x, position.y)
+        }
+        IcedEvent::Window(window::Event::Focused) => "focused".to_string(),
+        IcedEvent::Window(window::Event::Unfocused) => "unfocused".to_string(),
+        _ => return None,
+    };
+    Some(text)
+}
+
+// Reads an event written by `encode`.
+fn decode(tokens: &[&str], key_codes: &HashMap<String, KeyCode>) -> Option<IcedEvent> {
+    let button = || match *tokens.get(1)? {
+        "left" => Some(mouse::Button::Left),
+        "right" => Some(mouse::Button::Right),
+        "middle" => Some(mouse::Button::Middle),
+        other => other.parse().ok().map(mouse::Button::Other),
+    };
+    let key = || {
+        let key_code = *key_codes.get(*tokens.get(1)?)?;
+        Some((key_code, Modifiers::from_bits_truncate(number(tokens, 2)?)))
+    };
+    let finger = || Some((Finger(number(tokens, 1)?), point(tokens, 2)?));
+
+    let event = match *tokens.first()? {
+        "cursor_entered" => IcedEvent::Mouse(mouse::Event::CursorEntered),
+        "cursor_left" => IcedEvent::Mouse(mouse::Event::CursorLeft),
+        "cursor_moved" => IcedEvent::Mouse(mouse::Event::CursorMoved {
+            position: point(tokens, 1)?,
+        }),
+        "button_pressed" => IcedEvent::Mouse(mouse::Event::ButtonPressed(button()?)),
+        "button_released" => IcedEvent::Mouse(mouse::Event::ButtonReleased(button()?)),
+        "wheel_lines" => IcedEvent::Mouse(mouse::Event::WheelScrolled {
+            delta: mouse::ScrollDelta::Lines {
+                x: number(tokens, 1)?,
+                y: number(tokens, 2)?,
+            },
+        }),
+        "wheel_pixels" => IcedEvent::Mouse(mouse::Event::WheelScrolled {
+            delta: mouse::ScrollDelta::Pixels {
+                x: number(tokens, 1)?,
+                y: number(tokens, 2)?,
+            },
+        }),
+        "key_pressed" => {
+            let (key_code, modifiers) = key()?;
+            IcedEvent::Keyboard(keyboard::Event::KeyPressed {
+                key_code,
+                modifiers,
+            })
+        }
+        "key_released" => {
+            let (key_code, modifiers) = key()?;
+            IcedEvent::Keyboard(keyboard::Event::KeyReleased {
+                key_code,
+                modifiers,
+            })
+        }
+        "character" => IcedEvent::Keyboard(keyboard::Event::CharacterReceived(char::from_u32(
+            number(tokens, 1)?,
+        )?)),
+        "modifiers" => IcedEvent::Keyboard(keyboard::Event::ModifiersChanged(
+            Modifiers::from_bits_truncate(number(tokens, 1)?),
+        )),
+        "finger_pressed" => {
+            let (id, position) = finger()?;
+            IcedEvent::Touch(touch::Event::FingerPressed { id, position })
+        }
+        "finger_moved" => {
+            let (id, position) = finger()?;
+            IcedEvent::Touch(touch::Event::FingerMoved { id, position })
+        }
+        "finger_lifted" => {
+            let (id, position) = finger()?;
+            IcedEvent::Touch(touch::Event::FingerLifted { id, position })
+        }
+        "finger_lost" => {
+            let (id, position) = finger()?;
+            IcedEvent::Touch(touch::Event::FingerLost { id, position })
+        }
+        "focused" => IcedEvent::Window(window::Event::Focused),
+        "unfocused" => IcedEvent::Window(window::Event::Unfocused),
+        _ => return None,
+    };
+    Some(event)
+}
+
+fn number<T: FromStr>(tokens: &[&str], index: usize) -> Option<T> {
+    tokens.get(index)?.parse().ok()
+}
+
+fn point(tokens: &[&str], index: usize) -> Option<Point> {
+    Some(Point::new(
+        number(tokens, index)?,
+        number(tokens, index + 1)?,
+    ))
+}
+
+// The Iced key codes by name, found from the Bevy key codes they are converted from.
+fn key_codes() -> HashMap<String, KeyCode> {
+    let TypeInfo::Enum(info) = BevyKeyCode::type_info() else { return HashMap::default() };
+    info.variant_names()
+        .iter()
+        .filter_map(|name| {
+            let variant = DynamicEnum::new(info.type_name(), *name, DynamicVariant::Unit);
+            let key_code = conversions::key_code(BevyKeyCode::from_reflect(&variant)?);
+            Some((format!("{key_code:?}"), key_code))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_round_trip() {
+        let target = Entity::from_raw(3);
+        let frame = |cursors, events| RecordedFrame { cursors, events };
+        let recorder = IcedInputRecorder {
+            frames: vec![
+                frame(vec![(target, Point::new(1.5, -2.0))], vec![]),
+                frame(vec![], vec![]),
+                frame(
+                    vec![(target, Point::new(10.0, 20.25))],
+                    vec![
+                        (target, IcedEvent::Mouse(mouse::Event::CursorEntered)),
+                        (
+                            target,
+                            IcedEvent::Mouse(mouse::Event::ButtonPressed(mouse::Button::Other(8))),
+                        ),
+                        (
+                            target,
+                            IcedEvent::Mouse(mouse::Event::WheelScrolled {
+                                delta: mouse::ScrollDelta::Pixels { x: 0.5, y: -3.0 },
+                            }),
+                        ),
+                        (
+                            target,
+                            IcedEvent::Keyboard(keyboard::Event::KeyPressed {
+                                key_code: KeyCode::A,
+                                modifiers: Modifiers::SHIFT | Modifiers::CTRL,
+                            }),
+                        ),
+                        (
+                            target,
+                            IcedEvent::Keyboard(keyboard::Event::CharacterReceived('é')),
+                        ),
+                        (
+                            target,
+                            IcedEvent::Touch(touch::Event::FingerMoved {
+                                id: Finger(7),
+                                position: Point::new(4.0, 5.0),
+                            }),
+                        ),
+                        (target, IcedEvent::Window(window::Event::Unfocused)),
+                    ],
+                ),
+                frame(vec![], vec![]),
+            ],
+        };
+
+        let replay = IcedInputReplay::parse(&recorder.to_text()).unwrap();
+        assert_eq!(replay.len, recorder.len());
+        for (index, recorded) in recorder.frames.iter().enumerate() {
+            let replayed = replay.frames.get(&index);
+            assert_eq!(replayed.unwrap_or(&RecordedFrame::default()), recorded);
+        }
+    }
+
+    #[test]
+    fn frame_count_is_not_allocated() {
+        let text = format!(
+            "{HEADER}\nframes {}\n{} cursor 0 1 2\n",
+            usize::MAX,
+            usize::MAX - 1
+        );
+        let replay = IcedInputReplay::parse(&text).unwrap();
+        assert_eq!(replay.len, usize::MAX);
+        assert_eq!(replay.frames.len(), 1);
+        assert!(!replay.is_finished());
+    }
+
+    #[test]
+    fn frames_past_the_count_are_rejected() {
+        let text = format!("{HEADER}\nframes 1\n1 cursor 0 1 2\n");
+        assert!(IcedInputReplay::parse(&text).is_err());
+    }
+}
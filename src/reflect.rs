@@ -0,0 +1,73 @@
+use bevy_ecs::change_detection::DetectChanges;
+use bevy_ecs::prelude::{ReflectResource, ResMut, Resource};
+use bevy_reflect::{FromReflect, Reflect};
+use bevy_render::color::Color;
+
+use crate::IcedSettings;
+
+/// The built-in Iced themes, as reflected by [`IcedReflectedStyle`].
+#[derive(Reflect, FromReflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IcedThemeKind {
+    /// `Theme::Light`.
+    Light,
+    /// `Theme::Dark`.
+    #[default]
+    Dark,
+    /// A `Theme::Custom` palette, which can only be changed from code.
+    Custom,
+}
+
+impl IcedThemeKind {
+    fn of(theme: &iced_wgpu::Theme) -> Self {
+        match theme {
+            iced_wgpu::Theme::Light => Self::Light,
+            iced_wgpu::Theme::Dark => Self::Dark,
+            iced_wgpu::Theme::Custom(_) => Self::Custom,
+        }
+    }
+}
+
+/// The theme and text color of the default [`IcedSettings`], which Iced types can't expose to
+/// reflection themselves.
+///
+/// Both resources are kept in sync, so editing this one in `bevy-inspector-egui` or other
+/// reflection-based tools changes the theme of the UIs. The other settings, like the scale
+/// factor, are reflected by `IcedSettings` directly.
+#[derive(Resource, Reflect, Clone, Copy, Debug, Default, PartialEq)]
+#[reflect(Resource)]
+pub struct IcedReflectedStyle {
+    /// The theme of the UIs.
+    pub theme: IcedThemeKind,
+    /// The color of text that doesn't set one.
+    pub text_color: Color,
+}
+
+// Copies edits of the reflected style to the settings, and changes of the settings back.
+pub(crate) fn sync_reflected_style(
+    mut settings: ResMut<IcedSettings>,
+    mut reflected: ResMut<IcedReflectedStyle>,
+) {
+    if reflected.is_changed() && !reflected.is_added() {
+        let theme = match reflected.theme {
+            IcedThemeKind::Light => iced_wgpu::Theme::Light,
+            IcedThemeKind::Dark => iced_wgpu::Theme::Dark,
+            IcedThemeKind::Custom => settings.theme.clone(),
+        };
+        let [r, g, b, a] = reflected.text_color.as_rgba_f32();
+        let text_color = iced_native::Color::from_rgba(r, g, b, a);
+        // Inspectors may write the style back as it was, which mustn't lay out the UIs again.
+        if settings.theme != theme || settings.style.text_color != text_color {
+            settings.theme = theme;
+            settings.style.text_color = text_color;
+        }
+    }
+    if settings.is_changed() {
+        let iced_native::Color { r, g, b, a } = settings.style.text_color;
+        let style = IcedReflectedStyle {
+            theme: IcedThemeKind::of(&settings.theme),
+            text_color: Color::rgba(r, g, b, a),
+        };
+        // Not an edit, which would be copied back on the next frame.
+        *reflected.bypass_change_detection() = style;
+    }
+}
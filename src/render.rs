@@ -1,71 +1,358 @@
+use bevy_asset::{Assets, Handle};
 use bevy_derive::{Deref, DerefMut};
-use bevy_ecs::prelude::Query;
+use bevy_ecs::prelude::{DetectChanges, Entity, EventReader, Query, ResMut, With};
 use bevy_ecs::{
-    system::{Commands, Res, Resource},
+    system::{Commands, NonSendMut, Res, Resource},
     world::World,
 };
+use bevy_render::render_asset::RenderAssets;
 use bevy_render::renderer::RenderDevice;
+use bevy_render::texture::Image;
 use bevy_render::{
     render_graph::{Node, NodeRunError, RenderGraphContext},
     renderer::RenderContext,
     view::ExtractedWindows,
     Extract,
 };
-use bevy_window::Window;
-use iced_native::Size;
-use iced_wgpu::{wgpu::util::StagingBelt, Viewport};
+use bevy_utils::tracing::info_span;
+use bevy_utils::{HashMap, Instant};
+use bevy_window::{Window, WindowScaleFactorChanged};
+use iced_native::{alignment, Color, Font, Rectangle, Size};
+use iced_wgpu::wgpu::{self, util::StagingBelt, Extent3d, TextureFormat};
+use iced_wgpu::{Backend, Primitive, Settings, Viewport};
 use std::sync::Mutex;
 
-use crate::{DidDraw, IcedProps, IcedResource, IcedSettings};
+use crate::capture::{CaptureTarget, ExtractedCaptures, PendingReadbacks};
+use crate::diagnostics::IcedRenderTime;
+use crate::{
+    platform_settings, IcedClearColor, IcedDebug, IcedIdle, IcedLifecycle, IcedRenderTarget,
+    IcedRenderer, IcedSafeArea, IcedScaleFactor, IcedSettings,
+};
 
+/// The label of the node of the render graph that renders the UIs.
 pub const ICED_PASS: &str = "bevy_iced_pass";
 
-#[derive(Resource, Deref, DerefMut, Clone)]
-pub struct ViewportResource(pub Viewport);
+const STAGING_BELT_SIZE: u64 = 5 * 1024;
 
-pub(crate) fn update_viewport(
-    windows: Query<&Window>,
-    iced_settings: Res<IcedSettings>,
-    mut commands: Commands,
+/// The Iced viewport of every window and render target.
+#[derive(Resource, Deref, DerefMut, Clone, Default)]
+pub struct ViewportResource(pub HashMap<Entity, Viewport>);
+
+pub(crate) fn update_viewport<Theme: Send + Sync + 'static>(
+    windows: Query<(
+        Entity,
+        &Window,
+        Option<&IcedScaleFactor>,
+        Option<&IcedSettings<Theme>>,
+    )>,
+    targets: Query<(Entity, &IcedRenderTarget, Option<&IcedSettings<Theme>>)>,
+    images: Res<Assets<Image>>,
+    iced_settings: Res<IcedSettings<Theme>>,
+    safe_area: Res<IcedSafeArea>,
+    mut viewports: ResMut<ViewportResource>,
+) {
+    viewports.clear();
+    for (entity, window, scale_override, window_settings) in windows.iter() {
+        // Minimized windows are resized to nothing on some platforms. Without a viewport, the
+        // UIs of hidden and minimized windows are neither built nor rendered until they are
+        // shown again.
+        if !window.visible || window.physical_width() == 0 || window.physical_height() == 0 {
+            continue;
+        }
+        let iced_settings = window_settings.unwrap_or(&iced_settings);
+        let scale_factor = match iced_settings.design_size() {
+            // The design resolution fits into the area the UIs are laid out in, in physical
+            // pixels.
+            Some(design) => {
+                let size = Size::new(
+                    window.physical_width() as f32,
+                    window.physical_height() as f32,
+                );
+                let area = iced_settings
+                    .viewport_rect
+                    .map_or(Rectangle::with_size(size), |rect| rect.to_ui(window, 1.0));
+                let area = safe_area.shrink(area, window, size);
+                f32::min(area.width / design.width, area.height / design.height) as f64
+            }
+            None => scale_override
+                .map(|scale| scale.0)
+                .or(iced_settings.scale_factor)
+                .unwrap_or(window.scale_factor()),
+        };
+        // Nothing can be shown in an empty area.
+        if scale_factor <= 0.0 {
+            continue;
+        }
+        let viewport = Viewport::with_physical_size(
+            Size::new(window.physical_width(), window.physical_height()),
+            scale_factor,
+        );
+        viewports.insert(entity, viewport);
+    }
+    for (entity, target, target_settings) in targets.iter() {
+        let iced_settings = target_settings.unwrap_or(&iced_settings);
+        let Some(image) = images.get(&target.0) else { continue };
+        let size = image.size();
+        if size.x < 1.0 || size.y < 1.0 {
+            continue;
+        }
+        let viewport = Viewport::with_physical_size(
+            Size::new(size.x as u32, size.y as u32),
+            iced_settings.scale_factor.unwrap_or(1.0),
+        );
+        viewports.insert(entity, viewport);
+    }
+}
+
+/// Rebuilds the renderer used for layout when the renderer settings change, when a window moves
+/// to a monitor with another scale factor, so no text is measured with stale caches, when the
+/// render device is replaced after being lost, or when the caches are trimmed.
+pub(crate) fn update_renderer<Theme: Send + Sync + 'static>(
+    iced_settings: Res<IcedSettings<Theme>>,
+    mut scale_factor_changed: EventReader<WindowScaleFactorChanged>,
+    device: Option<Res<RenderDevice>>,
+    renderer: Option<NonSendMut<IcedRenderer<Theme>>>,
+    mut trim: ResMut<TrimCaches>,
 ) {
-    let window = windows.single();
-    let scale_factor = iced_settings.scale_factor.unwrap_or(window.scale_factor());
-    let viewport = Viewport::with_physical_size(
-        Size::new(window.physical_width(), window.physical_height()),
-        scale_factor,
-    );
-    commands.insert_resource(ViewportResource(viewport));
+    let rescaled = scale_factor_changed.iter().count() > 0;
+    let trimmed = std::mem::take(&mut **trim);
+    let (Some(device), Some(mut renderer)) = (device, renderer) else { return };
+    let settings = platform_settings(iced_settings.renderer);
+    // The renderer is built with the device in the plugin, and again if the device is replaced.
+    let device_replaced = device.is_changed() && !device.is_added();
+    if !same_settings(&settings, &renderer.settings) || rescaled || device_replaced || trimmed {
+        *renderer = IcedRenderer::new(&device, settings);
+    }
+}
+
+// Whether two renderer settings are the same, comparing the default fonts by address instead
+// of byte by byte every frame, since fonts are interned.
+pub(crate) fn same_settings(a: &Settings, b: &Settings) -> bool {
+    let font = |settings: &Settings| {
+        settings
+            .default_font
+            .map(|font| (font.as_ptr(), font.len()))
+    };
+    let without_font = |settings: &Settings| Settings {
+        default_font: None,
+        ..*settings
+    };
+    font(a) == font(b) && without_font(a) == without_font(b)
 }
 
-// Same as DidDraw, but as a regular bool instead of an atomic.
+// The primitives of one UI displayed in a window.
+#[derive(Clone)]
+pub(crate) struct Layer {
+    pub(crate) z: i32,
+    pub(crate) primitives: Vec<Primitive>,
+}
+
+// The layers displayed in each window during the current frame of the main world.
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct IcedFrames(HashMap<Entity, Vec<Layer>>);
+
+// The primitives drawn for each window this frame, cloned from `IcedFrames` during extraction.
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct ExtractedFrames(HashMap<Entity, Vec<Primitive>>);
+
+// The lines of the Iced debug overlay, drawn on top of each window.
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct ExtractedOverlay(Vec<String>);
+
+// The images of all `IcedRenderTarget` entities.
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct ExtractedRenderTargets(HashMap<Entity, Handle<Image>>);
+
+// The colors that windows and render targets are cleared to, with premultiplied alpha.
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct ExtractedClearColors(HashMap<Entity, wgpu::Color>);
+
+// The renderer settings of the main world, which the render node's backends are rebuilt with.
 #[derive(Resource, Deref, DerefMut)]
-struct DidDrawBasic(bool);
+pub(crate) struct ExtractedSettings(Settings);
+
+// Whether the application is suspended, in which case the UI isn't rendered.
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct Suspended(bool);
+
+// The characters whose glyphs are drawn invisibly in every window with the next frame, at a
+// size and in a font, see `IcedContext::warm_up_glyphs`.
+#[derive(Resource, Deref, DerefMut, Default)]
+pub(crate) struct GlyphWarmup(Vec<(String, f32, Font)>);
+
+/// Draws the characters to warm up below the UIs, in transparent text that is only rasterized
+/// into the glyph caches.
+pub(crate) fn draw_glyph_warmup(
+    mut warmup: ResMut<GlyphWarmup>,
+    viewports: Res<ViewportResource>,
+    windows: Query<(), With<Window>>,
+    mut frames: ResMut<IcedFrames>,
+) {
+    if warmup.is_empty() {
+        return;
+    }
+    let texts = std::mem::take(&mut **warmup);
+    for (window, viewport) in viewports.iter() {
+        if !windows.contains(*window) {
+            continue;
+        }
+        let bounds = Rectangle::with_size(viewport.logical_size());
+        let primitives = texts
+            .iter()
+            .map(|(content, size, font)| Primitive::Text {
+                content: content.clone(),
+                bounds,
+                color: Color::TRANSPARENT,
+                size: *size,
+                font: *font,
+                horizontal_alignment: alignment::Horizontal::Left,
+                vertical_alignment: alignment::Vertical::Top,
+            })
+            .collect();
+        frames.entry(*window).or_default().push(Layer {
+            z: i32::MIN,
+            primitives: vec![Primitive::Clip {
+                bounds,
+                content: Box::new(Primitive::Group { primitives }),
+            }],
+        });
+    }
+}
+
+// Whether the caches of the renderers are dropped this frame, see `IcedContext::trim_caches`.
+#[derive(Resource, Deref, DerefMut, Clone, Copy, Default)]
+pub(crate) struct TrimCaches(bool);
 
-pub(crate) fn extract_iced_data(
+pub(crate) fn extract_iced_data<Theme: Send + Sync + 'static>(
     mut commands: Commands,
+    iced_settings: Extract<Res<IcedSettings<Theme>>>,
     viewport: Extract<Res<ViewportResource>>,
-    did_draw: Extract<Res<DidDraw>>,
+    targets: Extract<Query<(Entity, &IcedRenderTarget)>>,
+    clear_colors: Extract<Query<(Entity, &IcedClearColor)>>,
+    captures: Extract<Query<(Entity, &CaptureTarget)>>,
+    mut lifecycle: Extract<EventReader<IcedLifecycle>>,
+    frames: Extract<Res<IcedFrames>>,
+    debug: Extract<Res<IcedDebug>>,
+    idle: Extract<Res<IcedIdle>>,
+    trim: Extract<Res<TrimCaches>>,
+    mut suspended: ResMut<Suspended>,
 ) {
+    let _span = info_span!("iced_extract").entered();
+    if let Some(event) = lifecycle.iter().last() {
+        **suspended = *event == IcedLifecycle::Suspended;
+    }
+
     commands.insert_resource(viewport.clone());
-    commands.insert_resource(DidDrawBasic(
-        did_draw.swap(false, std::sync::atomic::Ordering::Relaxed),
+    commands.insert_resource(ExtractedSettings(platform_settings(iced_settings.renderer)));
+    commands.insert_resource(ExtractedRenderTargets(
+        targets
+            .iter()
+            .map(|(entity, target)| (entity, target.0.clone_weak()))
+            .collect(),
+    ));
+    commands.insert_resource(ExtractedClearColors(
+        clear_colors
+            .iter()
+            .map(|(entity, IcedClearColor(color))| {
+                let [r, g, b, a] = color.as_linear_rgba_f32();
+                let (r, g, b, a) = (r as f64, g as f64, b as f64, a as f64);
+                (
+                    entity,
+                    wgpu::Color {
+                        r: r * a,
+                        g: g * a,
+                        b: b * a,
+                        a,
+                    },
+                )
+            })
+            .collect(),
+    ));
+    commands.insert_resource(ExtractedCaptures::extract(&captures));
+    commands.insert_resource(ExtractedOverlay(debug.debug.overlay()));
+    commands.insert_resource(**trim);
+    // No UI is displayed while idle, so the frames of the last active frame are drawn again.
+    if idle.is_idle() {
+        return;
+    }
+    commands.insert_resource(ExtractedFrames(
+        frames
+            .iter()
+            .map(|(target, layers)| {
+                let mut layers: Vec<_> = layers.iter().collect();
+                // The sort is stable, so layers with the same z are drawn in display order.
+                layers.sort_by_key(|layer| layer.z);
+                let primitives = layers
+                    .into_iter()
+                    .flat_map(|layer| layer.primitives.iter().cloned())
+                    .collect();
+                (*target, primitives)
+            })
+            .collect(),
     ));
 }
 
 pub struct IcedNode {
     staging_belt: Mutex<StagingBelt>,
+    // The wgpu pipelines depend on the texture format, so there's a backend for each format.
+    backends: Mutex<HashMap<TextureFormat, Backend>>,
+    settings: Settings,
+    texture_format: Option<TextureFormat>,
+    // The device that the backends were created with, kept alive to tell when it's replaced.
+    device: Option<RenderDevice>,
 }
 
 impl IcedNode {
-    pub fn new() -> Self {
+    pub fn new(settings: Settings, texture_format: Option<TextureFormat>) -> Self {
         Self {
-            staging_belt: Mutex::new(StagingBelt::new(5 * 1024)),
+            staging_belt: Mutex::new(StagingBelt::new(STAGING_BELT_SIZE)),
+            backends: Mutex::new(HashMap::new()),
+            settings,
+            texture_format,
+            device: None,
         }
     }
+
+    // Drops the GPU resources, which are recreated on the next render.
+    fn reset(&mut self) {
+        self.backends.get_mut().unwrap().clear();
+        *self.staging_belt.get_mut().unwrap() = StagingBelt::new(STAGING_BELT_SIZE);
+    }
 }
 
 impl Node for IcedNode {
-    fn update(&mut self, _world: &mut World) {
+    fn update(&mut self, world: &mut World) {
+        if world
+            .get_resource::<Suspended>()
+            .map_or(false, |suspended| **suspended)
+        {
+            // The surfaces are gone; drop the GPU resources and recreate them once resumed.
+            self.reset();
+            return;
+        }
+        if world
+            .get_resource::<TrimCaches>()
+            .map_or(false, |trim| **trim)
+        {
+            self.reset();
+        }
+        // After the device is lost, e.g. by a driver reset, the resources of the backends are
+        // dead, so they are rebuilt with the device that replaces it.
+        if let Some(device) = world.get_resource::<RenderDevice>() {
+            let replaced = self.device.as_ref().map_or(false, |old| {
+                !std::ptr::eq(old.wgpu_device(), device.wgpu_device())
+            });
+            if replaced {
+                self.reset();
+            }
+            self.device = Some(device.clone());
+        }
+        if let Some(settings) = world.get_resource::<ExtractedSettings>() {
+            if !same_settings(settings, &self.settings) {
+                self.settings = **settings;
+                self.backends.get_mut().unwrap().clear();
+            }
+        }
         self.staging_belt.lock().unwrap().recall()
     }
 
@@ -75,33 +362,81 @@ impl Node for IcedNode {
         render_context: &mut RenderContext,
         world: &World,
     ) -> Result<(), NodeRunError> {
-        let Some(extracted_window) = world
-            .get_resource::<ExtractedWindows>()
-            .unwrap()
-            .windows
-            .values()
-            .next() else { return Ok(()) };
-
-        let IcedProps {
-            renderer, debug, ..
-        } = &mut *world.resource::<IcedResource>().lock().unwrap();
-        let render_device = world.resource::<RenderDevice>();
-
-        if !world
-            .get_resource::<DidDrawBasic>()
-            .map(|x| x.0)
-            .unwrap_or(false)
-        {
+        let Some(frames) = world.get_resource::<ExtractedFrames>() else { return Ok(()) };
+        let clear_colors = world.resource::<ExtractedClearColors>();
+        let captures = world.resource::<ExtractedCaptures>();
+        let readbacks = world.resource::<PendingReadbacks>();
+        if (frames.is_empty() && clear_colors.is_empty()) || **world.resource::<Suspended>() {
             return Ok(());
         }
+        let _span = info_span!("iced_render").entered();
+        let start = Instant::now();
 
-        let view = extracted_window.swap_chain_texture.as_ref().unwrap();
+        let extracted_windows = &world.resource::<ExtractedWindows>().windows;
+        let render_targets = world.resource::<ExtractedRenderTargets>();
+        let images = world.resource::<RenderAssets<Image>>();
+        let viewports = world.resource::<ViewportResource>();
+        let overlay = &world.resource::<ExtractedOverlay>().0;
+        let render_device = world.resource::<RenderDevice>();
+        let device = render_device.wgpu_device();
         let staging_belt = &mut *self.staging_belt.lock().unwrap();
+        let backends = &mut *self.backends.lock().unwrap();
 
-        let viewport = world.resource::<ViewportResource>();
-        let device = render_device.wgpu_device();
+        // Cleared targets are also cleared while none of their UIs are displayed.
+        let targets = frames.keys().chain(
+            clear_colors
+                .keys()
+                .filter(|target| !frames.contains_key(*target)),
+        );
+        for target in targets {
+            let (view, format) = match extracted_windows.get(target) {
+                // The format override only applies to window surfaces, as the format of an image
+                // is known and the pipeline has to match it.
+                Some(extracted_window) => (
+                    extracted_window.swap_chain_texture.as_ref(),
+                    self.texture_format
+                        .or(extracted_window.swap_chain_texture_format),
+                ),
+                None => {
+                    let image = render_targets
+                        .get(target)
+                        .and_then(|image| images.get(image));
+                    (
+                        image.map(|image| &image.texture_view),
+                        image.map(|image| image.texture_format),
+                    )
+                }
+            };
+            let Some(view) = view else { continue };
+            if let Some(color) = clear_colors.get(target) {
+                render_context
+                    .command_encoder()
+                    .begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("iced_clear_pass"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(*color),
+                                store: true,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                    });
+            }
+            let (Some(primitives), Some(viewport)) = (frames.get(target), viewports.get(target))
+                else { continue };
 
-        renderer.with_primitives(|backend, primitives| {
+            let format = format.unwrap_or(TextureFormat::Bgra8UnormSrgb);
+            // The debug overlay is only drawn over the UIs of windows, not into images.
+            let overlay: &[String] = if extracted_windows.contains_key(target) {
+                overlay
+            } else {
+                &[]
+            };
+            let backend = backends
+                .entry(format)
+                .or_insert_with(|| Backend::new(device, self.settings, format));
             backend.present(
                 device,
                 staging_belt,
@@ -109,11 +444,33 @@ impl Node for IcedNode {
                 view,
                 primitives,
                 viewport,
-                &debug.overlay(),
+                overlay,
             );
-        });
+            let capture = captures.get(target).zip(
+                render_targets
+                    .get(target)
+                    .and_then(|image| images.get(image)),
+            );
+            if let Some((capture, image)) = capture {
+                let size = Extent3d {
+                    width: image.size.x as u32,
+                    height: image.size.y as u32,
+                    depth_or_array_layers: 1,
+                };
+                readbacks.copy(
+                    render_device,
+                    render_context.command_encoder(),
+                    &image.texture,
+                    size,
+                    capture.clone(),
+                );
+            }
+        }
 
         staging_belt.finish();
+        if let Some(render_time) = world.get_resource::<IcedRenderTime>() {
+            render_time.set(start.elapsed());
+        }
 
         Ok(())
     }
@@ -0,0 +1,113 @@
+use bevy_ecs::prelude::{Entity, EventReader, Query, Res, ResMut, Resource, With};
+use bevy_input::touch::{ForceTouch, TouchInput, TouchPhase};
+use bevy_window::{PrimaryWindow, Window};
+use iced_native::{mouse, Event as IcedEvent, Point};
+
+use crate::render::ViewportResource;
+use crate::systems::{focused_window, window_to_ui, IcedSyntheticInput};
+
+/// The state of the pen or stylus touching the focused window.
+///
+/// Requires the `touch` feature. Bevy reports pens as touches with a force, which are forwarded
+/// as Iced touch events. Since those events carry no pressure or tilt, they are stored here for
+/// canvas programs to read while handling them, e.g. to vary the width of a stroke. Touches
+/// that report a force are treated as pen input, which includes fingers on some iOS devices.
+///
+/// A pen hovering over the screen is reported as a mouse cursor by most platforms, and moves
+/// the cursor of the UIs like a mouse.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct IcedStylus {
+    /// Whether the pen also moves the cursor and presses the left mouse button, so widgets that
+    /// only handle mouse input, like buttons and most canvas programs, work with pens.
+    pub emulate_mouse: bool,
+    // The touch of the pen, while it touches the screen.
+    touch: Option<u64>,
+    window: Option<Entity>,
+    position: Option<Point>,
+    pressure: Option<f32>,
+    altitude_angle: Option<f32>,
+}
+
+impl IcedStylus {
+    /// Whether the pen touches the screen.
+    pub fn is_touching(&self) -> bool {
+        self.touch.is_some()
+    }
+
+    /// The window the pen touches.
+    pub fn window(&self) -> Option<Entity> {
+        self.window
+    }
+
+    /// The position of the pen, in logical UI coordinates from the top-left corner.
+    pub fn position(&self) -> Option<Point> {
+        self.position
+    }
+
+    /// How hard the pen is pressed, from 0 to 1.
+    pub fn pressure(&self) -> Option<f32> {
+        self.pressure
+    }
+
+    /// The angle between the pen and the screen in radians, from 0 when it lies flat on the
+    /// screen to π/2 when it is upright. Only reported by some platforms, like iOS.
+    pub fn altitude_angle(&self) -> Option<f32> {
+        self.altitude_angle
+    }
+}
+
+/// Tracks the pen touching the focused window, and emulates the mouse with it if enabled.
+pub(crate) fn process_stylus_input(
+    mut stylus: ResMut<IcedStylus>,
+    mut touch_input: EventReader<TouchInput>,
+    windows: Query<(Entity, &Window)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    viewports: Res<ViewportResource>,
+    mut synthetic: ResMut<IcedSyntheticInput>,
+) {
+    let Some(window) = focused_window(&windows, &primary_window) else { return };
+    let (Ok((_, bevy_window)), Some(viewport)) = (windows.get(window), viewports.get(&window))
+        else { return };
+
+    for ev in touch_input.iter() {
+        let Some(force) = ev.force else { continue };
+        if stylus.touch.map_or(false, |touch| touch != ev.id) {
+            continue;
+        }
+        let position = window_to_ui(bevy_window, viewport.logical_size(), ev.position);
+        let (pressure, altitude_angle) = match force {
+            ForceTouch::Calibrated {
+                force,
+                max_possible_force,
+                altitude_angle,
+            } => (force / max_possible_force, altitude_angle),
+            ForceTouch::Normalized(force) => (force, None),
+        };
+        let touching = matches!(ev.phase, TouchPhase::Started | TouchPhase::Moved);
+        *stylus = IcedStylus {
+            touch: touching.then_some(ev.id),
+            window: Some(window),
+            position: Some(position),
+            pressure: Some(pressure as f32),
+            altitude_angle: altitude_angle.map(|angle| angle as f32),
+            ..*stylus
+        };
+
+        if stylus.emulate_mouse {
+            synthetic.events.push((
+                window,
+                IcedEvent::Mouse(mouse::Event::CursorMoved { position }),
+            ));
+            let button = match ev.phase {
+                TouchPhase::Started => Some(mouse::Event::ButtonPressed(mouse::Button::Left)),
+                TouchPhase::Ended | TouchPhase::Cancelled => {
+                    Some(mouse::Event::ButtonReleased(mouse::Button::Left))
+                }
+                TouchPhase::Moved => None,
+            };
+            if let Some(button) = button {
+                synthetic.events.push((window, IcedEvent::Mouse(button)));
+            }
+        }
+    }
+}
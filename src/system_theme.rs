@@ -0,0 +1,127 @@
+use bevy_app::{App, CoreSet, Plugin};
+use bevy_ecs::prelude::{Entity, EventReader, EventWriter, Local, NonSend, Query, ResMut, With};
+use bevy_ecs::schedule::IntoSystemConfig;
+use bevy_utils::HashMap;
+use bevy_window::{PrimaryWindow, Window};
+use bevy_winit::WinitWindows;
+
+use crate::IcedSettings;
+
+/// The appearance that the OS prefers for a window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IcedSystemTheme {
+    /// Dark text on light backgrounds.
+    Light,
+    /// Light text on dark backgrounds.
+    Dark,
+}
+
+impl From<IcedSystemTheme> for iced_wgpu::Theme {
+    fn from(theme: IcedSystemTheme) -> Self {
+        match theme {
+            IcedSystemTheme::Light => iced_wgpu::Theme::Light,
+            IcedSystemTheme::Dark => iced_wgpu::Theme::Dark,
+        }
+    }
+}
+
+/// Sent when the OS theme of a window is first known, and every time it changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IcedSystemThemeChanged {
+    /// The window.
+    pub window: Entity,
+    /// Its new theme.
+    pub theme: IcedSystemTheme,
+}
+
+/// Reads the light or dark theme that the OS prefers for each window from winit, and sends
+/// [`IcedSystemThemeChanged`] events when it changes.
+///
+/// Requires the `system_theme` feature. Add it after [`IcedPlugin`](crate::IcedPlugin). While
+/// `follow` is set, the theme of the default [`IcedSettings`] switches between
+/// `Theme::Light` and `Theme::Dark` with the theme of the primary window. Apps with their own
+/// theme type, or that let players pick the theme, disable it and handle the events instead:
+/// ```ignore
+/// app.add_plugin(IcedPlugin::default())
+///     .add_plugin(IcedSystemThemePlugin { follow: false })
+///     .add_system(follow_theme);
+///
+/// fn follow_theme(
+///     mut changes: EventReader<IcedSystemThemeChanged>,
+///     mut settings: ResMut<IcedSettings<MyTheme>>,
+/// ) {
+///     for change in changes.iter() {
+///         settings.theme = MyTheme::from(change.theme);
+///     }
+/// }
+/// ```
+///
+/// Not every platform reports a theme, e.g. X11 and the web, in which case no event is sent.
+pub struct IcedSystemThemePlugin {
+    /// Whether the default [`IcedSettings`] follow the theme of the primary window.
+    pub follow: bool,
+}
+
+impl Default for IcedSystemThemePlugin {
+    fn default() -> Self {
+        Self { follow: true }
+    }
+}
+
+impl IcedSystemThemePlugin {
+    // Bevy doesn't forward winit's theme events, so the theme of every window is polled.
+    fn detect_system_themes(
+        winit_windows: Option<NonSend<WinitWindows>>,
+        windows: Query<Entity, With<Window>>,
+        mut themes: Local<HashMap<Entity, IcedSystemTheme>>,
+        mut changed: EventWriter<IcedSystemThemeChanged>,
+    ) {
+        let Some(winit_windows) = winit_windows else { return };
+        themes.retain(|window, _| windows.contains(*window));
+        for window in windows.iter() {
+            let Some(theme) = winit_windows
+                .get_window(window)
+                .and_then(|winit_window| winit_window.theme())
+                else { continue };
+            let theme = match theme {
+                winit::window::Theme::Light => IcedSystemTheme::Light,
+                winit::window::Theme::Dark => IcedSystemTheme::Dark,
+            };
+            if themes.insert(window, theme) != Some(theme) {
+                changed.send(IcedSystemThemeChanged { window, theme });
+            }
+        }
+    }
+
+    fn follow_system_theme(
+        mut changed: EventReader<IcedSystemThemeChanged>,
+        primary_window: Query<Entity, With<PrimaryWindow>>,
+        mut settings: ResMut<IcedSettings>,
+    ) {
+        let Ok(primary_window) = primary_window.get_single() else { return };
+        for change in changed
+            .iter()
+            .filter(|change| change.window == primary_window)
+        {
+            let theme = iced_wgpu::Theme::from(change.theme);
+            // Only changed settings lay out the UIs again.
+            if settings.theme != theme {
+                settings.theme = theme;
+            }
+        }
+    }
+}
+
+impl Plugin for IcedSystemThemePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<IcedSystemThemeChanged>()
+            .add_system(Self::detect_system_themes.in_base_set(CoreSet::PreUpdate));
+        if self.follow {
+            app.add_system(
+                Self::follow_system_theme
+                    .after(Self::detect_system_themes)
+                    .in_base_set(CoreSet::PreUpdate),
+            );
+        }
+    }
+}
@@ -1,22 +1,239 @@
-use crate::conversions;
-use bevy_derive::{Deref, DerefMut};
+use crate::diagnostics::IcedTimings;
+use crate::render::{IcedFrames, ViewportResource};
+use crate::{
+    conversions, IcedCache, IcedCamera, IcedCursorIcons, IcedCursorMode, IcedCursorPolicy,
+    IcedDebug, IcedDisplayResult, IcedDoubleClick, IcedEventFilters, IcedIdle, IcedImePreedit,
+    IcedKeyMapping, IcedKeyRepeat, IcedOcclusionRects, IcedRenderTarget, IcedScrollConfig,
+    IcedSettings, InputConsumptionPolicy,
+};
 use bevy_ecs::{
-    prelude::EventReader,
-    system::{Res, ResMut, Resource, SystemParam},
+    change_detection::DetectChanges,
+    prelude::{Entity, EventReader, Local, Query, With},
+    system::{NonSendMut, Res, ResMut, Resource, SystemParam},
 };
 use bevy_input::keyboard::KeyCode;
 #[cfg(feature = "touch")]
 use bevy_input::touch::TouchInput;
 use bevy_input::{
     keyboard::KeyboardInput,
-    mouse::{MouseButtonInput, MouseWheel},
+    mouse::{MouseButton, MouseButtonInput, MouseScrollUnit, MouseWheel},
     ButtonState, Input,
 };
-use bevy_window::{CursorEntered, CursorLeft, CursorMoved, ReceivedCharacter};
-use iced_native::{keyboard, mouse, Event as IcedEvent, Point};
+use bevy_math::Vec2;
+use bevy_render::camera::{Camera, NormalizedRenderTarget};
+use bevy_time::Time;
+use bevy_utils::tracing::info_span;
+use bevy_utils::{HashMap, HashSet};
+use bevy_window::{
+    CursorEntered, CursorGrabMode, CursorLeft, CursorMoved, FileDragAndDrop, Ime, PrimaryWindow,
+    ReceivedCharacter, Window, WindowFocused,
+};
+use iced_native::{keyboard, mouse, window, Event as IcedEvent, Point, Rectangle, Size};
+use std::time::Duration;
+
+/// Pending Iced events, grouped by the window they were received in.
+///
+/// Mouse and touch positions are in the coordinates of the UIs of the window, like their cursor
+/// position, and are moved to the area of each UI when it is displayed.
+#[derive(Resource, Default)]
+pub struct IcedEventQueue(HashMap<Entity, Vec<iced_native::Event>>);
+
+impl IcedEventQueue {
+    pub fn push(&mut self, window: Entity, event: IcedEvent) {
+        self.0.entry(window).or_default().push(event);
+    }
+
+    pub fn get(&self, window: Entity) -> &[IcedEvent] {
+        self.0.get(&window).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn retain_window(&mut self, window: Entity, f: impl FnMut(&IcedEvent) -> bool) {
+        if let Some(events) = self.0.get_mut(&window) {
+            events.retain(f);
+        }
+    }
 
-#[derive(Resource, Deref, DerefMut, Default)]
-pub struct IcedEventQueue(Vec<iced_native::Event>);
+    pub fn retain(&mut self, mut f: impl FnMut(&IcedEvent) -> bool) {
+        self.0.values_mut().for_each(|events| events.retain(&mut f));
+    }
+
+    pub fn clear(&mut self) {
+        self.0.values_mut().for_each(Vec::clear);
+    }
+
+    pub fn filter_map(&mut self, mut f: impl FnMut(Entity, IcedEvent) -> Option<IcedEvent>) {
+        for (window, events) in self.0.iter_mut() {
+            *events = std::mem::take(events)
+                .into_iter()
+                .filter_map(|event| f(*window, event))
+                .collect();
+        }
+    }
+
+    pub fn windows(&self) -> impl Iterator<Item = (Entity, &[IcedEvent])> {
+        self.0
+            .iter()
+            .map(|(window, events)| (*window, events.as_slice()))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &IcedEvent> {
+        self.0.values().flatten()
+    }
+}
+
+/// Events and cursor positions injected by code rather than received from Bevy's input, with
+/// [`IcedContext::push_event`](crate::IcedContext::push_event) or the
+/// [`IcedTestHarness`](crate::testing::IcedTestHarness).
+#[derive(Resource, Default)]
+pub(crate) struct IcedSyntheticInput {
+    pub(crate) events: Vec<(Entity, IcedEvent)>,
+    // Replaces the cursor position of the window, until the real cursor moves in it.
+    pub(crate) cursors: HashMap<Entity, Point>,
+}
+
+/// Adds the injected events to the queue, after it was refilled for this frame.
+pub(crate) fn inject_synthetic_input(
+    mut synthetic: ResMut<IcedSyntheticInput>,
+    mut event_queue: ResMut<IcedEventQueue>,
+    mut cursor_moved: EventReader<CursorMoved>,
+) {
+    let IcedSyntheticInput { events, cursors } = &mut *synthetic;
+    for ev in cursor_moved.iter() {
+        cursors.remove(&ev.window);
+    }
+    for (window, event) in events.drain(..) {
+        if let IcedEvent::Mouse(mouse::Event::CursorMoved { position }) = event {
+            cursors.insert(window, position);
+        }
+        event_queue.push(window, event);
+    }
+}
+
+/// Repeats the presses of held keys in the focused window, as configured by [`IcedKeyRepeat`].
+pub(crate) fn repeat_keys(
+    config: Res<IcedKeyRepeat>,
+    time: Res<Time>,
+    keyboard_state: Res<IcedKeyboardState>,
+    windows: Query<&Window>,
+    mut event_queue: ResMut<IcedEventQueue>,
+    mut held: Local<HashMap<keyboard::KeyCode, (Entity, Duration)>>,
+) {
+    if !config.enabled {
+        held.clear();
+        return;
+    }
+    let now = time.elapsed();
+    let receivers: Vec<_> = event_queue.windows().map(|(window, _)| window).collect();
+    for window in receivers {
+        event_queue.retain_window(window, |event| match event {
+            IcedEvent::Keyboard(keyboard::Event::KeyPressed { key_code, .. }) => {
+                // Presses of held keys are repeats sent by the platform.
+                if held.contains_key(key_code) {
+                    return false;
+                }
+                held.insert(*key_code, (window, now + config.delay));
+                true
+            }
+            IcedEvent::Keyboard(keyboard::Event::KeyReleased { key_code, .. }) => {
+                held.remove(key_code);
+                true
+            }
+            _ => true,
+        });
+    }
+
+    // Releases aren't received once the window loses focus.
+    held.retain(|_, (window, _)| windows.get(*window).map_or(false, |window| window.focused));
+    let modifiers = keyboard_state.modifiers();
+    for (key_code, (window, next)) in held.iter_mut() {
+        if now >= *next {
+            event_queue.push(
+                *window,
+                IcedEvent::Keyboard(keyboard::Event::KeyPressed {
+                    key_code: *key_code,
+                    modifiers,
+                }),
+            );
+            *next = (*next + config.interval).max(now);
+        }
+    }
+}
+
+/// Moves mouse presses close to the previous press to its position, so that Iced widgets
+/// detect double and triple clicks as configured by [`IcedDoubleClick`].
+pub(crate) fn snap_double_clicks(
+    config: Res<IcedDoubleClick>,
+    time: Res<Time>,
+    event_queue: Res<IcedEventQueue>,
+    windows: Query<(Entity, &Window)>,
+    viewports: Res<ViewportResource>,
+    mut synthetic: ResMut<IcedSyntheticInput>,
+    mut last_presses: Local<HashMap<Entity, (Duration, Point)>>,
+) {
+    let now = time.elapsed();
+    for (entity, window) in windows.iter() {
+        let pressed = event_queue
+            .get(entity)
+            .iter()
+            .any(|event| matches!(event, IcedEvent::Mouse(mouse::Event::ButtonPressed(_))));
+        if !pressed {
+            continue;
+        }
+        let position = synthetic.cursors.get(&entity).copied().or_else(|| {
+            let viewport = viewports.get(&entity)?;
+            let position = window.cursor_position()?;
+            let position = Vec2::new(position.x, window.height() - position.y);
+            Some(window_to_ui(window, viewport.logical_size(), position))
+        });
+        let Some(position) = position else { continue };
+
+        match last_presses.get(&entity) {
+            Some(&(at, last_position))
+                if now - at <= config.interval
+                    && position.distance(last_position) <= config.max_distance =>
+            {
+                synthetic.cursors.insert(entity, last_position);
+                last_presses.insert(entity, (now, last_position));
+            }
+            _ => {
+                last_presses.insert(entity, (now, position));
+            }
+        }
+    }
+}
+
+/// Runs the [`IcedEventFilters`] on the events of this frame.
+pub(crate) fn filter_events(
+    mut filters: ResMut<IcedEventFilters>,
+    mut event_queue: ResMut<IcedEventQueue>,
+) {
+    if !filters.is_empty() {
+        event_queue.filter_map(|window, event| filters.apply(window, event));
+    }
+}
+
+/// The events captured by the UIs displayed this frame.
+#[derive(Resource, Default)]
+pub struct IcedCapturedInput(pub(crate) Vec<IcedEvent>);
+
+/// The mouse interactions of the UIs displayed this frame, by window.
+#[derive(Resource, Default)]
+pub struct IcedInteractions {
+    current: HashMap<Entity, mouse::Interaction>,
+    // Windows whose cursor icon was set for a UI interaction.
+    overridden: HashSet<Entity>,
+}
+
+impl IcedInteractions {
+    /// Records the interaction of a UI, keeping the interaction of any other UI in the window
+    /// that isn't idle.
+    pub(crate) fn insert(&mut self, window: Entity, interaction: mouse::Interaction) {
+        let current = self.current.entry(window).or_default();
+        if *current == mouse::Interaction::Idle {
+            *current = interaction;
+        }
+    }
+}
 
 #[derive(SystemParam)]
 pub struct InputEvents<'w, 's> {
@@ -27,84 +244,593 @@ pub struct InputEvents<'w, 's> {
     mouse_wheel: EventReader<'w, 's, MouseWheel>,
     received_character: EventReader<'w, 's, ReceivedCharacter>,
     keyboard_input: EventReader<'w, 's, KeyboardInput>,
+    window_focused: EventReader<'w, 's, WindowFocused>,
+    file_drag_and_drop: EventReader<'w, 's, FileDragAndDrop>,
+    ime: EventReader<'w, 's, Ime>,
     #[cfg(feature = "touch")]
     touch_input: EventReader<'w, 's, TouchInput>,
 }
 
-fn compute_modifiers(input_map: &Input<KeyCode>) -> keyboard::Modifiers {
-    let mut modifiers = keyboard::Modifiers::default();
-    if input_map.any_pressed([KeyCode::LControl, KeyCode::RControl]) {
-        modifiers |= keyboard::Modifiers::CTRL;
+/// The modifier keys held down and the keyboard layout, tracked from the keyboard events
+/// received by the UIs.
+#[derive(Resource, Default)]
+pub(crate) struct IcedKeyboardState {
+    held: HashSet<KeyCode>,
+    // The letter and digit keys typed by scan codes in the current layout.
+    layout: HashMap<u32, keyboard::KeyCode>,
+    // The Bevy key that each Iced key pressed this frame was converted from, to consume it
+    // when the press is captured.
+    pressed: HashMap<keyboard::KeyCode, KeyCode>,
+}
+
+impl IcedKeyboardState {
+    pub(crate) fn modifiers(&self) -> keyboard::Modifiers {
+        self.held
+            .iter()
+            .filter_map(|key| modifier(*key))
+            .fold(keyboard::Modifiers::empty(), |modifiers, m| modifiers | m)
+    }
+
+    // Returns the new modifiers if they changed.
+    fn update(&mut self, key: KeyCode, pressed: bool) -> Option<keyboard::Modifiers> {
+        let before = self.modifiers();
+        if pressed {
+            self.held.insert(key);
+        } else {
+            self.held.remove(&key);
+        }
+        let after = self.modifiers();
+        (after != before).then_some(after)
+    }
+
+    // Releases all modifiers, as releases aren't received while a window isn't focused.
+    fn release_all(&mut self) -> Option<keyboard::Modifiers> {
+        let before = self.modifiers();
+        self.held.clear();
+        (!before.is_empty()).then_some(keyboard::Modifiers::empty())
+    }
+
+    fn key_code(&self, key: KeyCode, scan_code: u32, mapping: IcedKeyMapping) -> keyboard::KeyCode {
+        match mapping {
+            IcedKeyMapping::Logical => self.layout.get(&scan_code).copied(),
+            IcedKeyMapping::Physical => conversions::physical_key_code(scan_code),
+        }
+        .unwrap_or_else(|| conversions::key_code(key))
+    }
+}
+
+fn is_modifier(key: KeyCode) -> bool {
+    modifier(key).is_some()
+}
+
+fn modifier(key: KeyCode) -> Option<keyboard::Modifiers> {
+    match key {
+        KeyCode::LControl | KeyCode::RControl => Some(keyboard::Modifiers::CTRL),
+        KeyCode::LShift | KeyCode::RShift => Some(keyboard::Modifiers::SHIFT),
+        KeyCode::LAlt | KeyCode::RAlt => Some(keyboard::Modifiers::ALT),
+        KeyCode::LWin | KeyCode::RWin => Some(keyboard::Modifiers::LOGO),
+        _ => None,
+    }
+}
+
+/// Starts counting the display calls of the new frame, and clears the layers and timings of
+/// the last one.
+pub(crate) fn new_frame(
+    mut cache: NonSendMut<IcedCache>,
+    mut frames: ResMut<IcedFrames>,
+    mut timings: ResMut<IcedTimings>,
+    mut result: ResMut<IcedDisplayResult>,
+    idle: Res<IcedIdle>,
+) {
+    // No UI is displayed while idle, so the frame isn't counted towards cache eviction.
+    if !idle.is_idle() {
+        cache.new_frame();
     }
-    if input_map.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
-        modifiers |= keyboard::Modifiers::SHIFT;
+    frames.clear();
+    *timings = IcedTimings::default();
+    result.new_frame();
+}
+
+/// Grabs and hides, or releases and shows, the cursor of the primary window when the
+/// [`IcedCursorMode`] changes.
+pub(crate) fn apply_cursor_mode(
+    mode: Res<IcedCursorMode>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if !mode.is_changed() {
+        return;
     }
-    if input_map.any_pressed([KeyCode::LAlt, KeyCode::RAlt]) {
-        modifiers |= keyboard::Modifiers::ALT;
+    let Ok(mut window) = windows.get_single_mut() else { return };
+    let (grab_mode, visible) = match *mode {
+        IcedCursorMode::Unmanaged => return,
+        IcedCursorMode::Ui => (CursorGrabMode::None, true),
+        IcedCursorMode::Game(grab_mode) => (grab_mode, false),
+    };
+    window.cursor.grab_mode = grab_mode;
+    window.cursor.visible = visible;
+}
+
+/// Whether the cursor of the window is grabbed and hidden, as in first-person games, in which
+/// case the UIs don't use it.
+pub(crate) fn cursor_grabbed(window: &Window) -> bool {
+    window.cursor.grab_mode != CursorGrabMode::None && !window.cursor.visible
+}
+
+/// Hit-tests the layers of the previous frame against the current cursor position, so that
+/// [`IcedDisplayResult::wants_pointer_input`] is up to date before the UIs are displayed again.
+pub(crate) fn hit_test_previous_frame(
+    frames: Res<IcedFrames>,
+    viewports: Res<ViewportResource>,
+    windows: Query<(Entity, &Window)>,
+    mut result: ResMut<IcedDisplayResult>,
+) {
+    result.wants_pointer_input = windows.iter().any(|(entity, window)| {
+        let Some(position) = window.cursor_position() else { return false };
+        !cursor_grabbed(window) && hit_test_window(&frames, &viewports, entity, window, position)
+    });
+}
+
+/// Whether a position in a window, in logical pixels from its bottom-left corner like its
+/// cursor position, is over something that its UIs drew during the previous frame.
+pub(crate) fn hit_test_window(
+    frames: &IcedFrames,
+    viewports: &ViewportResource,
+    entity: Entity,
+    window: &Window,
+    position: Vec2,
+) -> bool {
+    let (Some(layers), Some(viewport)) = (frames.get(&entity), viewports.get(&entity))
+        else { return false };
+    let position = Vec2::new(position.x, window.height() - position.y);
+    let cursor_position = window_to_ui(window, viewport.logical_size(), position);
+    layers.iter().any(|layer| {
+        layer
+            .primitives
+            .iter()
+            .any(|primitive| crate::hit_test(primitive, cursor_position))
+    })
+}
+
+/// Toggles Iced's debug overlay to match [`IcedSettings::show_debug_overlay`].
+pub(crate) fn toggle_debug_overlay<Theme: Send + Sync + 'static>(
+    settings: Res<IcedSettings<Theme>>,
+    mut debug: ResMut<IcedDebug>,
+) {
+    if settings.show_debug_overlay != debug.enabled {
+        debug.enabled = settings.show_debug_overlay;
+        debug.debug.toggle();
     }
-    if input_map.any_pressed([KeyCode::LWin, KeyCode::RWin]) {
-        modifiers |= keyboard::Modifiers::LOGO;
+}
+
+/// Publishes the occlusion rectangles gathered during the previous frame.
+pub(crate) fn swap_occlusion_rects(mut occlusion: ResMut<IcedOcclusionRects>) {
+    occlusion.swap();
+}
+
+/// Removes the input captured by the UIs from Bevy's `Input` resources.
+pub(crate) fn consume_captured_input(
+    policy: Res<InputConsumptionPolicy>,
+    mut captured: ResMut<IcedCapturedInput>,
+    keyboard_state: Res<IcedKeyboardState>,
+    mut mouse: ResMut<Input<MouseButton>>,
+    mut keys: ResMut<Input<KeyCode>>,
+) {
+    for event in captured.0.drain(..) {
+        match event {
+            IcedEvent::Mouse(mouse::Event::ButtonPressed(button)) if policy.mouse_buttons => {
+                let pressed: Vec<_> = mouse.get_just_pressed().copied().collect();
+                for bevy_button in pressed {
+                    if conversions::mouse_button(bevy_button) == Some(button) {
+                        mouse.reset(bevy_button);
+                    }
+                }
+            }
+            IcedEvent::Mouse(mouse::Event::ButtonReleased(button)) if policy.mouse_buttons => {
+                let released: Vec<_> = mouse.get_just_released().copied().collect();
+                for bevy_button in released {
+                    if conversions::mouse_button(bevy_button) == Some(button) {
+                        mouse.clear_just_released(bevy_button);
+                    }
+                }
+            }
+            IcedEvent::Keyboard(keyboard::Event::KeyPressed { key_code, .. })
+                if policy.keyboard =>
+            {
+                // The Iced key depends on the key mapping, so it's not converted back.
+                let bevy_key = keyboard_state.pressed.get(&key_code).copied();
+                if let Some(bevy_key) = bevy_key.filter(|key| keys.just_pressed(*key)) {
+                    keys.reset(bevy_key);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Shows the cursor icons of the UI interactions, according to the [`IcedCursorPolicy`] and
+/// [`IcedCursorIcons`].
+pub(crate) fn update_cursor_icons(
+    policy: Res<IcedCursorPolicy>,
+    icons: Res<IcedCursorIcons>,
+    mut interactions: ResMut<IcedInteractions>,
+    mut windows: Query<&mut Window>,
+) {
+    let IcedInteractions {
+        current,
+        overridden,
+    } = &mut *interactions;
+    for (entity, interaction) in current.drain() {
+        let Ok(mut window) = windows.get_mut(entity) else { continue };
+        let icon = match *policy {
+            IcedCursorPolicy::Never => continue,
+            IcedCursorPolicy::Always => icons.icon(interaction),
+            IcedCursorPolicy::NonIdle if interaction != mouse::Interaction::Idle => {
+                overridden.insert(entity);
+                icons.icon(interaction)
+            }
+            IcedCursorPolicy::NonIdle if overridden.remove(&entity) => icons.icon(interaction),
+            IcedCursorPolicy::NonIdle => continue,
+        };
+        // Avoid marking the window as changed every frame.
+        if window.cursor.icon != icon {
+            window.cursor.icon = icon;
+        }
     }
-    modifiers
 }
 
+/// Asks every UI to redraw with the time of this frame, so that animated widgets advance.
+pub(crate) fn request_redraw(
+    time: Res<Time>,
+    windows: Query<Entity, With<Window>>,
+    targets: Query<Entity, With<IcedRenderTarget>>,
+    mut event_queue: ResMut<IcedEventQueue>,
+) {
+    let now = time
+        .last_update()
+        .unwrap_or_else(iced_native::time::Instant::now);
+    for entity in windows.iter().chain(targets.iter()) {
+        event_queue.push(
+            entity,
+            IcedEvent::Window(window::Event::RedrawRequested(now)),
+        );
+    }
+}
+
+/// Tells the UIs that their window or render target was resized, when the logical size of its
+/// viewport changed since the last frame.
+pub(crate) fn send_resize_events(
+    viewports: Res<ViewportResource>,
+    windows: Query<(), With<Window>>,
+    mut sizes: Local<HashMap<Entity, Size<u32>>>,
+    mut event_queue: ResMut<IcedEventQueue>,
+) {
+    // Minimized windows have no viewport, but keep their size to compare once restored.
+    sizes.retain(|entity, _| viewports.contains_key(entity) || windows.contains(*entity));
+    for (entity, viewport) in viewports.iter() {
+        let logical_size = viewport.logical_size();
+        let size = Size::new(
+            logical_size.width.round() as u32,
+            logical_size.height.round() as u32,
+        );
+        if sizes
+            .insert(*entity, size)
+            .map_or(false, |previous| previous != size)
+        {
+            event_queue.push(
+                *entity,
+                IcedEvent::Window(window::Event::Resized {
+                    width: size.width,
+                    height: size.height,
+                }),
+            );
+        }
+    }
+}
+
+/// Converts a logical window position, with the origin in the top-left corner, into the
+/// coordinates of a UI with the given bounds.
+pub(crate) fn window_to_ui(window: &Window, bounds: Size, position: Vec2) -> Point {
+    Point::new(
+        position.x * bounds.width / window.width(),
+        position.y * bounds.height / window.height(),
+    )
+}
+
+/// Converts a point in the coordinates of a UI with the given bounds into a logical window
+/// position, with the origin in the top-left corner.
+pub(crate) fn ui_to_window(window: &Window, bounds: Size, position: Point) -> Vec2 {
+    Vec2::new(
+        position.x * window.width() / bounds.width,
+        position.y * window.height() / bounds.height,
+    )
+}
+
+/// The window a camera renders to, if it renders to one.
+pub(crate) fn camera_window(camera: &Camera, primary_window: Option<Entity>) -> Option<Entity> {
+    match camera.target.normalize(primary_window)? {
+        NormalizedRenderTarget::Window(window_ref) => Some(window_ref.entity()),
+        NormalizedRenderTarget::Image(_) => None,
+    }
+}
+
+/// The viewport of a camera, in the coordinates of the UIs of the window it renders to.
+pub(crate) fn camera_area(camera: &Camera, window: &Window, bounds: Size) -> Option<Rectangle> {
+    let (min, max) = camera.logical_viewport_rect()?;
+    let (scale_x, scale_y) = (
+        bounds.width / window.width(),
+        bounds.height / window.height(),
+    );
+    Some(Rectangle {
+        x: min.x * scale_x,
+        y: min.y * scale_y,
+        width: (max.x - min.x) * scale_x,
+        height: (max.y - min.y) * scale_y,
+    })
+}
+
+/// Gives the UIs of each [`IcedCamera`] the input of its window that belongs to it: the mouse
+/// while the cursor is in its viewport, the keyboard if it takes it, and window events.
+///
+/// The events are copied, so UIs displayed in the whole window still receive them.
+pub(crate) fn route_camera_input(
+    cameras: Query<(Entity, &Camera, &IcedCamera)>,
+    windows: Query<(Entity, &Window)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    viewports: Res<ViewportResource>,
+    synthetic: Res<IcedSyntheticInput>,
+    mut event_queue: ResMut<IcedEventQueue>,
+) {
+    let primary_window = primary_window.get_single().ok();
+    let mut routed = Vec::new();
+    for (window, bevy_window) in windows.iter() {
+        let Some(viewport) = viewports.get(&window) else { continue };
+        let bounds = viewport.logical_size();
+        let mut in_window: Vec<_> = cameras
+            .iter()
+            .filter(|(_, camera, _)| camera_window(camera, primary_window) == Some(window))
+            .filter_map(|(entity, camera, config)| {
+                let area = camera_area(camera, bevy_window, bounds)?;
+                Some((entity, camera.order, area, config.keyboard))
+            })
+            .collect();
+        if in_window.is_empty() {
+            continue;
+        }
+        // The camera drawn on top gets the cursor where viewports overlap.
+        in_window.sort_by_key(|(_, order, _, _)| std::cmp::Reverse(*order));
+        let cursor = synthetic.cursors.get(&window).copied().or_else(|| {
+            let Vec2 { x, y } = bevy_window.cursor_position()?;
+            let position = Vec2::new(x, bevy_window.height() - y);
+            Some(window_to_ui(bevy_window, bounds, position))
+        });
+        let hovered = cursor.and_then(|cursor| {
+            in_window
+                .iter()
+                .find(|(_, _, area, _)| area.contains(cursor))
+                .map(|(entity, ..)| *entity)
+        });
+
+        for event in event_queue.get(window) {
+            for &(camera, _, _, keyboard) in &in_window {
+                let receives = match event {
+                    IcedEvent::Keyboard(_) => keyboard,
+                    IcedEvent::Mouse(_) | IcedEvent::Touch(_) => hovered == Some(camera),
+                    _ => true,
+                };
+                if receives {
+                    routed.push((camera, event.clone()));
+                }
+            }
+        }
+    }
+    for (camera, event) in routed {
+        event_queue.push(camera, event);
+    }
+}
+
+/// Returns the focused window, falling back to the primary window.
+pub(crate) fn focused_window(
+    windows: &Query<(Entity, &Window)>,
+    primary_window: &Query<Entity, With<PrimaryWindow>>,
+) -> Option<Entity> {
+    windows
+        .iter()
+        .find(|(_, window)| window.focused)
+        .map(|(entity, _)| entity)
+        .or_else(|| primary_window.get_single().ok())
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn process_input(
     mut events: InputEvents,
     mut event_queue: ResMut<IcedEventQueue>,
-    input_map: Res<Input<KeyCode>>,
+    mut keyboard_state: ResMut<IcedKeyboardState>,
+    key_mapping: Res<IcedKeyMapping>,
+    scroll: Res<IcedScrollConfig>,
+    windows: Query<(Entity, &Window)>,
+    primary_window: Query<Entity, With<PrimaryWindow>>,
+    mut preedit: ResMut<IcedImePreedit>,
+    viewports: Res<ViewportResource>,
+    mut typed: Local<HashMap<Entity, Vec<char>>>,
+    mut keyboard_input: Local<Vec<KeyboardInput>>,
 ) {
+    let _span = info_span!("iced_process_input").entered();
     event_queue.clear();
 
+    // Events that don't carry a window are routed to the focused window (keyboard)
+    // or to the window under the cursor (mouse), falling back to the primary window.
+    let focused = focused_window(&windows, &primary_window);
+    let hovered = windows
+        .iter()
+        .find(|(_, window)| window.cursor_position().is_some())
+        .map(|(entity, _)| entity)
+        .or(focused);
+
+    // Positions are given in the space of the window UIs, like the cursor position of the UIs.
     for ev in events.cursor.iter() {
-        event_queue.push(IcedEvent::Mouse(mouse::Event::CursorMoved {
-            position: Point::new(ev.position.x, ev.position.y),
-        }));
+        let (Ok((_, window)), Some(viewport)) = (windows.get(ev.window), viewports.get(&ev.window))
+            else { continue };
+        let position = Vec2::new(ev.position.x, window.height() - ev.position.y);
+        event_queue.push(
+            ev.window,
+            IcedEvent::Mouse(mouse::Event::CursorMoved {
+                position: window_to_ui(window, viewport.logical_size(), position),
+            }),
+        );
     }
 
     for ev in events.mouse_button.iter() {
-        let button = conversions::mouse_button(ev.button);
-        event_queue.push(IcedEvent::Mouse(match ev.state {
-            ButtonState::Pressed => iced_native::mouse::Event::ButtonPressed(button),
-            ButtonState::Released => iced_native::mouse::Event::ButtonReleased(button),
-        }))
+        let Some(window) = hovered else { continue };
+        let Some(button) = conversions::mouse_button(ev.button) else { continue };
+        event_queue.push(
+            window,
+            IcedEvent::Mouse(match ev.state {
+                ButtonState::Pressed => iced_native::mouse::Event::ButtonPressed(button),
+                ButtonState::Released => iced_native::mouse::Event::ButtonReleased(button),
+            }),
+        )
     }
 
-    for _ev in events.cursor_entered.iter() {
-        event_queue.push(IcedEvent::Mouse(iced_native::mouse::Event::CursorEntered));
+    for ev in events.cursor_entered.iter() {
+        event_queue.push(
+            ev.window,
+            IcedEvent::Mouse(iced_native::mouse::Event::CursorEntered),
+        );
     }
 
-    for _ev in events.cursor_left.iter() {
-        event_queue.push(IcedEvent::Mouse(iced_native::mouse::Event::CursorLeft));
+    for ev in events.cursor_left.iter() {
+        event_queue.push(
+            ev.window,
+            IcedEvent::Mouse(iced_native::mouse::Event::CursorLeft),
+        );
     }
 
     for ev in events.mouse_wheel.iter() {
-        event_queue.push(IcedEvent::Mouse(iced_native::mouse::Event::WheelScrolled {
-            delta: mouse::ScrollDelta::Pixels { x: ev.x, y: ev.y },
-        }));
+        let Some(window) = hovered else { continue };
+        let (x, y) = scroll.apply(ev.x, ev.y, keyboard_state.modifiers().shift());
+        let delta = match ev.unit {
+            MouseScrollUnit::Line => mouse::ScrollDelta::Lines {
+                x: x * scroll.line_multiplier,
+                y: y * scroll.line_multiplier,
+            },
+            MouseScrollUnit::Pixel => mouse::ScrollDelta::Pixels {
+                x: x * scroll.pixel_multiplier,
+                y: y * scroll.pixel_multiplier,
+            },
+        };
+        event_queue.push(
+            window,
+            IcedEvent::Mouse(iced_native::mouse::Event::WheelScrolled { delta }),
+        );
     }
 
+    // The buffers of the previous frame are cleared, keeping their allocations.
+    typed.values_mut().for_each(Vec::clear);
     for ev in events.received_character.iter() {
-        event_queue.push(IcedEvent::Keyboard(
-            iced_native::keyboard::Event::CharacterReceived(ev.char),
-        ));
+        typed.entry(ev.window).or_default().push(ev.char);
+        event_queue.push(
+            ev.window,
+            IcedEvent::Keyboard(iced_native::keyboard::Event::CharacterReceived(ev.char)),
+        );
     }
 
-    for ev in events.keyboard_input.iter() {
+    // Neither Bevy's nor Iced's file events carry a position, and the cursor position of the
+    // window usually isn't updated during the drag, so the drop position is unavailable.
+    for ev in events.file_drag_and_drop.iter() {
+        let (window, event) = match ev {
+            FileDragAndDrop::HoveredFile { window, path_buf } => {
+                (window, window::Event::FileHovered(path_buf.clone()))
+            }
+            FileDragAndDrop::DroppedFile { window, path_buf } => {
+                (window, window::Event::FileDropped(path_buf.clone()))
+            }
+            FileDragAndDrop::HoveredFileCancelled { window } => {
+                (window, window::Event::FilesHoveredLeft)
+            }
+        };
+        event_queue.push(*window, IcedEvent::Window(event));
+    }
+
+    for ev in events.ime.iter() {
+        match ev {
+            Ime::Preedit {
+                window,
+                value,
+                cursor,
+            } => {
+                *preedit = IcedImePreedit {
+                    window: Some(*window),
+                    value: value.clone(),
+                    cursor: *cursor,
+                };
+            }
+            Ime::Commit { window, value } => {
+                for c in value.chars() {
+                    event_queue.push(
+                        *window,
+                        IcedEvent::Keyboard(iced_native::keyboard::Event::CharacterReceived(c)),
+                    );
+                }
+                *preedit = IcedImePreedit::default();
+            }
+            Ime::Disabled { .. } => *preedit = IcedImePreedit::default(),
+            Ime::Enabled { .. } => {}
+        }
+    }
+
+    for ev in events.window_focused.iter() {
+        event_queue.push(
+            ev.window,
+            IcedEvent::Window(if ev.focused {
+                window::Event::Focused
+            } else {
+                window::Event::Unfocused
+            }),
+        );
+        if ev.focused {
+            continue;
+        }
+        if let Some(changed) = keyboard_state.release_all() {
+            event_queue.push(
+                ev.window,
+                IcedEvent::Keyboard(keyboard::Event::ModifiersChanged(changed)),
+            );
+        }
+    }
+
+    keyboard_input.clear();
+    keyboard_input.extend(events.keyboard_input.iter().cloned());
+    keyboard_state.pressed.clear();
+    if let (Some(window), IcedKeyMapping::Logical) = (focused, *key_mapping) {
+        // Learn which key types a character when it is the only key pressed this frame, so the
+        // key is still recognized when it is pressed with Ctrl and types nothing.
+        let mut presses = keyboard_input
+            .iter()
+            .filter(|ev| ev.state.is_pressed() && !ev.key_code.map_or(false, is_modifier));
+        let chars = typed.get(&window).map(Vec::as_slice).unwrap_or_default();
+        if let (Some(press), None, [c]) = (presses.next(), presses.next(), chars) {
+            if let Some(key_code) = conversions::char_key_code(*c) {
+                keyboard_state.layout.insert(press.scan_code, key_code);
+            }
+        }
+    }
+
+    for ev in keyboard_input.iter() {
+        let Some(window) = focused else { continue };
         if let Some(code) = ev.key_code {
-            use keyboard::Event::*;
-            let modifiers = compute_modifiers(&input_map);
+            use iced_native::keyboard::Event::*;
             let event = match code {
-                KeyCode::LControl
-                | KeyCode::RControl
-                | KeyCode::LShift
-                | KeyCode::RShift
-                | KeyCode::LAlt
-                | KeyCode::RAlt
-                | KeyCode::LWin
-                | KeyCode::RWin => ModifiersChanged(modifiers),
+                code if is_modifier(code) => {
+                    let Some(changed) = keyboard_state.update(code, ev.state.is_pressed()) else {
+                        continue;
+                    };
+                    ModifiersChanged(changed)
+                }
                 code => {
-                    let key_code = conversions::key_code(code);
+                    let modifiers = keyboard_state.modifiers();
+                    let key_code = keyboard_state.key_code(code, ev.scan_code, *key_mapping);
                     if ev.state.is_pressed() {
+                        keyboard_state.pressed.insert(key_code, code);
                         KeyPressed {
                             key_code,
                             modifiers,
@@ -118,12 +844,19 @@ pub fn process_input(
                 }
             };
 
-            event_queue.push(IcedEvent::Keyboard(event));
+            event_queue.push(window, IcedEvent::Keyboard(event));
         }
     }
 
     #[cfg(feature = "touch")]
     for ev in events.touch_input.iter() {
-        event_queue.push(IcedEvent::Touch(conversions::touch_event(ev)));
+        let Some(window) = focused else { continue };
+        let (Ok((_, bevy_window)), Some(viewport)) =
+            (windows.get(window), viewports.get(&window)) else { continue };
+        let position = window_to_ui(bevy_window, viewport.logical_size(), ev.position);
+        event_queue.push(
+            window,
+            IcedEvent::Touch(conversions::touch_event(ev, position)),
+        );
     }
 }
@@ -0,0 +1,590 @@
+//! Headless tests of UI logic, driven by synthetic input.
+//!
+//! [`IcedTestHarness`] wraps an [`App`] with the [`IcedPlugin`](crate::IcedPlugin), injects
+//! cursor moves, clicks and key presses into the UIs, advances frames and collects the messages
+//! they emitted:
+//! ```ignore
+//! let mut app = App::new();
+//! app.add_plugins(
+//!     DefaultPlugins
+//!         .build()
+//!         .disable::<WinitPlugin>()
+//!         .set(WindowPlugin { primary_window: None, ..default() }),
+//! )
+//! .add_plugin(IcedPlugin::default())
+//! .add_event::<UiMessage>()
+//! .add_system(ui_system);
+//!
+//! let mut harness = IcedTestHarness::new(app);
+//! harness.click(Point::new(40.0, 20.0));
+//! harness.update();
+//! assert_eq!(harness.messages::<UiMessage>(), [UiMessage::Clicked]);
+//! ```
+//!
+//! [`IcedTestHarness::snapshot`] captures the primitives drawn in the last frame as
+//! [`PrimitiveSnapshot`]s, which can be compared to a golden file to catch layout regressions
+//! without reading back from the GPU. Enable the `serde` feature to serialize them.
+//! [`IcedTestHarness::export_svg`] draws the frame as an SVG document instead, to review it or
+//! diff it visually.
+//!
+//! The layout of the UIs is computed by the Iced renderer, so the app still needs Bevy's
+//! `RenderPlugin` and a render device, which a software adapter provides on machines without
+//! a GPU. Without one, the UIs are not displayed and no messages are emitted.
+
+use bevy_app::App;
+use bevy_ecs::event::{Event, Events};
+use bevy_ecs::prelude::{Entity, With};
+use bevy_window::{PrimaryWindow, Window};
+use iced_native::keyboard::{self, KeyCode, Modifiers};
+use iced_native::{
+    alignment, mouse, Background, Color, Event as IcedEvent, Point, Rectangle, Size,
+};
+use iced_wgpu::Primitive;
+
+use crate::render::{IcedFrames, ViewportResource};
+use crate::systems::IcedSyntheticInput;
+
+/// Drives an [`App`] displaying Iced UIs with synthetic input, for tests.
+///
+/// Injected input is received by the UIs on the next call to [`IcedTestHarness::update`].
+pub struct IcedTestHarness {
+    app: App,
+    window: Entity,
+}
+
+impl IcedTestHarness {
+    /// Wraps the app, spawning a primary window entity if it has none.
+    ///
+    /// The window doesn't need to be opened by `WinitPlugin`; its size in the [`Window`]
+    /// component is used as the size of the UIs.
+    pub fn new(mut app: App) -> Self {
+        let primary = app
+            .world
+            .query_filtered::<Entity, With<PrimaryWindow>>()
+            .get_single(&app.world)
+            .ok();
+        let window =
+            primary.unwrap_or_else(|| app.world.spawn((Window::default(), PrimaryWindow)).id());
+        Self { app, window }
+    }
+
+    /// The wrapped app.
+    pub fn app(&mut self) -> &mut App {
+        &mut self.app
+    }
+
+    /// The window the input is injected into.
+    pub fn window(&self) -> Entity {
+        self.window
+    }
+
+    /// Injects input into another window from now on.
+    pub fn set_window(&mut self, window: Entity) {
+        self.window = window;
+    }
+
+    /// Runs one frame of the app.
+    pub fn update(&mut self) {
+        self.app.update();
+    }
+
+    /// Runs the given number of frames of the app.
+    pub fn update_frames(&mut self, frames: u32) {
+        for _ in 0..frames {
+            self.app.update();
+        }
+    }
+
+    /// Injects an Iced event into the window.
+    pub fn push_event(&mut self, event: IcedEvent) {
+        let window = self.window;
+        self.synthetic_input().events.push((window, event));
+    }
+
+    /// Moves the cursor to a position in the logical coordinates of the window, where it stays
+    /// until it is moved again.
+    pub fn move_cursor(&mut self, position: Point) {
+        self.push_event(IcedEvent::Mouse(mouse::Event::CursorMoved { position }));
+    }
+
+    /// Presses and releases a mouse button at the current position of the cursor.
+    pub fn press_button(&mut self, button: mouse::Button) {
+        self.push_event(IcedEvent::Mouse(mouse::Event::ButtonPressed(button)));
+        self.push_event(IcedEvent::Mouse(mouse::Event::ButtonReleased(button)));
+    }
+
+    /// Moves the cursor to a position and clicks the left mouse button there.
+    pub fn click(&mut self, position: Point) {
+        self.move_cursor(position);
+        self.press_button(mouse::Button::Left);
+    }
+
+    /// Presses and releases a key without modifiers.
+    pub fn press_key(&mut self, key_code: KeyCode) {
+        self.press_key_with(key_code, Modifiers::empty());
+    }
+
+    /// Presses and releases a key while holding the given modifiers.
+    pub fn press_key_with(&mut self, key_code: KeyCode, modifiers: Modifiers) {
+        self.push_event(IcedEvent::Keyboard(keyboard::Event::ModifiersChanged(
+            modifiers,
+        )));
+        self.push_event(IcedEvent::Keyboard(keyboard::Event::KeyPressed {
+            key_code,
+            modifiers,
+        }));
+        self.push_event(IcedEvent::Keyboard(keyboard::Event::KeyReleased {
+            key_code,
+            modifiers,
+        }));
+        if !modifiers.is_empty() {
+            self.push_event(IcedEvent::Keyboard(keyboard::Event::ModifiersChanged(
+                Modifiers::empty(),
+            )));
+        }
+    }
+
+    /// Types text into the focused widget, one character at a time.
+    pub fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.push_event(IcedEvent::Keyboard(keyboard::Event::CharacterReceived(c)));
+        }
+    }
+
+    /// Takes the messages of type `M` emitted since the last call, leaving none for the
+    /// systems of the app.
+    pub fn messages<M: Event>(&mut self) -> Vec<M> {
+        self.app.world.resource_mut::<Events<M>>().drain().collect()
+    }
+
+    /// The primitives drawn in the window by the last frame that ran the UI systems, with one
+    /// snapshot for each displayed UI, from the bottom layer to the top one.
+    pub fn snapshot(&self) -> Vec<PrimitiveSnapshot> {
+        let Some(layers) = self.app.world.resource::<IcedFrames>().get(&self.window) else {
+            return Vec::new();
+        };
+        let mut layers: Vec<_> = layers.iter().collect();
+        layers.sort_by_key(|layer| layer.z);
+        layers
+            .into_iter()
+            .map(|layer| PrimitiveSnapshot::Group(snapshot_all(&layer.primitives)))
+            .collect()
+    }
+
+    /// The primitives drawn in the window by the last frame that ran the UI systems, as an SVG
+    /// document, see [`export_svg`].
+    pub fn export_svg(&self) -> String {
+        let size = self
+            .app
+            .world
+            .resource::<ViewportResource>()
+            .get(&self.window)
+            .map_or(Size::ZERO, |viewport| viewport.logical_size());
+        let mut layers = self
+            .app
+            .world
+            .resource::<IcedFrames>()
+            .get(&self.window)
+            .map_or(Vec::new(), |layers| layers.iter().collect());
+        layers.sort_by_key(|layer| layer.z);
+        export_svg(layers.iter().flat_map(|layer| &layer.primitives), size)
+    }
+
+    fn synthetic_input(&mut self) -> &mut IcedSyntheticInput {
+        self.app
+            .world
+            .resource_mut::<IcedSyntheticInput>()
+            .into_inner()
+    }
+}
+
+/// A stable description of the primitives drawn by a UI, to compare in golden tests.
+///
+/// Coordinates are in logical pixels, rounded to hundredths so that tiny floating point
+/// differences don't fail the comparison. Rectangles are `[x, y, width, height]` and colors
+/// are RGBA bytes. Empty primitives are skipped and cached ones are replaced by their content.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PrimitiveSnapshot {
+    /// A group of primitives, drawn in order.
+    Group(Vec<PrimitiveSnapshot>),
+    /// A rectangle, e.g. the background of a button.
+    Quad {
+        /// The bounds of the quad.
+        bounds: [f32; 4],
+        /// The color of the background, if it is a solid color.
+        background: Option<[u8; 4]>,
+        /// The radius of each corner.
+        border_radius: [f32; 4],
+        /// The width of the border.
+        border_width: f32,
+        /// The color of the border.
+        border_color: [u8; 4],
+    },
+    /// A line of text.
+    Text {
+        /// The text.
+        content: String,
+        /// The bounds of the text.
+        bounds: [f32; 4],
+        /// The size of the text.
+        size: f32,
+        /// The color of the text.
+        color: [u8; 4],
+    },
+    /// An image.
+    Image {
+        /// The bounds of the image.
+        bounds: [f32; 4],
+    },
+    /// A vector image.
+    Svg {
+        /// The bounds of the image.
+        bounds: [f32; 4],
+    },
+    /// Primitives clipped to a rectangle.
+    Clip {
+        /// The clipping rectangle.
+        bounds: [f32; 4],
+        /// The clipped primitive.
+        content: Box<PrimitiveSnapshot>,
+    },
+    /// Primitives moved by an offset.
+    Translate {
+        /// The offset, as `[x, y]`.
+        translation: [f32; 2],
+        /// The moved primitive.
+        content: Box<PrimitiveSnapshot>,
+    },
+    /// A primitive without a detailed snapshot, like a mesh.
+    Other,
+}
+
+impl PrimitiveSnapshot {
+    /// Describes a primitive, or returns `None` if it draws nothing.
+    pub fn new(primitive: &Primitive) -> Option<Self> {
+        let snapshot = match primitive {
+            Primitive::None => return None,
+            Primitive::Group { primitives } => Self::Group(snapshot_all(primitives)),
+            Primitive::Quad {
+                bounds,
+                background,
+                border_radius,
+                border_width,
+                border_color,
+            } => Self::Quad {
+                bounds: rect(bounds),
+                background: match background {
+                    Background::Color(color) => Some(rgba(*color)),
+                    #[allow(unreachable_patterns)]
+                    _ => None,
+                },
+                border_radius: border_radius.map(round),
+                border_width: round(*border_width),
+                border_color: rgba(*border_color),
+            },
+            Primitive::Text {
+                content,
+                bounds,
+                size,
+                color,
+                ..
+            } => Self::Text {
+                content: content.clone(),
+                bounds: rect(bounds),
+                size: round(*size),
+                color: rgba(*color),
+            },
+            Primitive::Image { bounds, .. } => Self::Image {
+                bounds: rect(bounds),
+            },
+            Primitive::Svg { bounds, .. } => Self::Svg {
+                bounds: rect(bounds),
+            },
+            Primitive::Clip { bounds, content } => Self::Clip {
+                bounds: rect(bounds),
+                content: Box::new(Self::new(content).unwrap_or(Self::Group(Vec::new()))),
+            },
+            Primitive::Translate {
+                translation,
+                content,
+            } => Self::Translate {
+                translation: [round(translation.x), round(translation.y)],
+                content: Box::new(Self::new(content).unwrap_or(Self::Group(Vec::new()))),
+            },
+            Primitive::Cache { content } => return Self::new(content),
+            _ => Self::Other,
+        };
+        Some(snapshot)
+    }
+}
+
+/// Draws primitives as an SVG document of the given logical size, e.g. to review a layout, or
+/// diff it between changes, without a GPU.
+///
+/// Quads and text are drawn with their colors, and images, vector images and meshes as gray
+/// placeholders of their bounds. Text is drawn in the generic sans-serif font of the viewer, so
+/// its width differs from the Iced layout.
+pub fn export_svg<'a>(primitives: impl IntoIterator<Item = &'a Primitive>, size: Size) -> String {
+    let (width, height) = (round(size.width), round(size.height));
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"0 0 {width} {height}\">\n"
+    );
+    let mut clips = 0;
+    for primitive in primitives {
+        write_svg(primitive, &mut svg, &mut clips);
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+// Appends the SVG elements of a primitive, numbering the clip paths with `clips`.
+fn write_svg(primitive: &Primitive, svg: &mut String, clips: &mut usize) {
+    match primitive {
+        Primitive::Group { primitives } => primitives
+            .iter()
+            .for_each(|primitive| write_svg(primitive, svg, clips)),
+        Primitive::Quad {
+            bounds,
+            background,
+            border_radius,
+            border_width,
+            border_color,
+        } => {
+            let fill = match background {
+                Background::Color(color) => svg_paint("fill", *color),
+                #[allow(unreachable_patterns)]
+                _ => "fill=\"none\"".to_owned(),
+            };
+            let stroke = if *border_width > 0.0 {
+                format!(
+                    "{} stroke-width=\"{}\"",
+                    svg_paint("stroke", *border_color),
+                    round(*border_width)
+                )
+            } else {
+                String::new()
+            };
+            svg.push_str(&format!(
+                "<rect {} rx=\"{}\" {fill} {stroke}/>\n",
+                svg_bounds(bounds),
+                round(border_radius[0])
+            ));
+        }
+        // Transparent text is only drawn to warm up the glyph caches.
+        Primitive::Text {
+            content,
+            bounds,
+            color,
+            size,
+            horizontal_alignment,
+            vertical_alignment,
+            ..
+        } if color.a > 0.0 => {
+            let anchor = match horizontal_alignment {
+                alignment::Horizontal::Left => "start",
+                alignment::Horizontal::Center => "middle",
+                alignment::Horizontal::Right => "end",
+            };
+            let baseline = match vertical_alignment {
+                alignment::Vertical::Top => "text-before-edge",
+                alignment::Vertical::Center => "central",
+                alignment::Vertical::Bottom => "text-after-edge",
+            };
+            svg.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-family=\"sans-serif\" font-size=\"{}\" \
+                 text-anchor=\"{anchor}\" dominant-baseline=\"{baseline}\" {}>{}</text>\n",
+                round(bounds.x),
+                round(bounds.y),
+                round(*size),
+                svg_paint("fill", *color),
+                escape_svg(content)
+            ));
+        }
+        Primitive::Image { bounds, .. } | Primitive::Svg { bounds, .. } => {
+            svg.push_str(&svg_placeholder(bounds))
+        }
+        Primitive::SolidMesh { size, .. } | Primitive::GradientMesh { size, .. } => {
+            svg.push_str(&svg_placeholder(&Rectangle::with_size(*size)))
+        }
+        Primitive::Clip { bounds, content } => {
+            *clips += 1;
+            let id = *clips;
+            svg.push_str(&format!(
+                "<clipPath id=\"clip{id}\"><rect {}/></clipPath>\n\
+                 <g clip-path=\"url(#clip{id})\">\n",
+                svg_bounds(bounds)
+            ));
+            write_svg(content, svg, clips);
+            svg.push_str("</g>\n");
+        }
+        Primitive::Translate {
+            translation,
+            content,
+        } => {
+            svg.push_str(&format!(
+                "<g transform=\"translate({} {})\">\n",
+                round(translation.x),
+                round(translation.y)
+            ));
+            write_svg(content, svg, clips);
+            svg.push_str("</g>\n");
+        }
+        Primitive::Cache { content } => write_svg(content, svg, clips),
+        _ => {}
+    }
+}
+
+fn svg_bounds(bounds: &Rectangle) -> String {
+    let [x, y, width, height] = rect(bounds);
+    format!("x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\"")
+}
+
+fn svg_paint(attribute: &str, color: Color) -> String {
+    let [r, g, b, _] = rgba(color);
+    format!(
+        "{attribute}=\"rgb({r},{g},{b})\" {attribute}-opacity=\"{}\"",
+        round(color.a)
+    )
+}
+
+fn svg_placeholder(bounds: &Rectangle) -> String {
+    format!(
+        "<rect {} fill=\"#ccc\" stroke=\"#888\" stroke-dasharray=\"4\"/>\n",
+        svg_bounds(bounds)
+    )
+}
+
+fn escape_svg(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn snapshot_all(primitives: &[Primitive]) -> Vec<PrimitiveSnapshot> {
+    primitives
+        .iter()
+        .filter_map(PrimitiveSnapshot::new)
+        .collect()
+}
+
+// Rounds to hundredths, turning `-0.0` into `0.0`.
+fn round(value: f32) -> f32 {
+    (value * 100.0).round() / 100.0 + 0.0
+}
+
+fn rect(bounds: &Rectangle) -> [f32; 4] {
+    [bounds.x, bounds.y, bounds.width, bounds.height].map(round)
+}
+
+fn rgba(color: Color) -> [u8; 4] {
+    color.into_rgba8()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(bounds: Rectangle, color: Color) -> Primitive {
+        Primitive::Quad {
+            bounds,
+            background: Background::Color(color),
+            border_radius: [0.0; 4],
+            border_width: 0.0,
+            border_color: Color::TRANSPARENT,
+        }
+    }
+
+    fn text(content: &str, color: Color) -> Primitive {
+        Primitive::Text {
+            content: content.to_owned(),
+            bounds: Rectangle::new(Point::new(5.0, 6.0), Size::new(100.0, 20.0)),
+            color,
+            size: 20.0,
+            font: Default::default(),
+            horizontal_alignment: alignment::Horizontal::Left,
+            vertical_alignment: alignment::Vertical::Top,
+        }
+    }
+
+    #[test]
+    fn svg_of_quad() {
+        let quad = quad(
+            Rectangle::new(Point::new(1.0, 2.0), Size::new(30.0, 40.0)),
+            Color::from_rgb(1.0, 0.0, 0.0),
+        );
+        let svg = export_svg([&quad], Size::new(200.0, 100.0));
+        assert_eq!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"200\" height=\"100\" \
+             viewBox=\"0 0 200 100\">\n\
+             <rect x=\"1\" y=\"2\" width=\"30\" height=\"40\" rx=\"0\" \
+             fill=\"rgb(255,0,0)\" fill-opacity=\"1\" />\n\
+             </svg>\n"
+        );
+    }
+
+    #[test]
+    fn svg_of_text_is_escaped() {
+        let svg = export_svg([&text("a < b & c", Color::BLACK)], Size::new(200.0, 100.0));
+        assert!(svg.contains(
+            "<text x=\"5\" y=\"6\" font-family=\"sans-serif\" font-size=\"20\" \
+             text-anchor=\"start\" dominant-baseline=\"text-before-edge\" \
+             fill=\"rgb(0,0,0)\" fill-opacity=\"1\">a &lt; b &amp; c</text>\n"
+        ));
+    }
+
+    #[test]
+    fn svg_skips_transparent_text() {
+        let svg = export_svg(
+            [&text("hidden", Color::TRANSPARENT)],
+            Size::new(200.0, 100.0),
+        );
+        assert!(!svg.contains("<text"));
+    }
+
+    #[test]
+    fn svg_of_clips() {
+        let clip = |size| Primitive::Clip {
+            bounds: Rectangle::with_size(Size::new(size, size)),
+            content: Box::new(quad(
+                Rectangle::with_size(Size::new(50.0, 50.0)),
+                Color::WHITE,
+            )),
+        };
+        let svg = export_svg([&clip(10.0), &clip(20.0)], Size::new(200.0, 100.0));
+        assert!(svg.contains(
+            "<clipPath id=\"clip1\"><rect x=\"0\" y=\"0\" width=\"10\" height=\"10\"/>\
+             </clipPath>\n<g clip-path=\"url(#clip1)\">\n<rect x=\"0\" y=\"0\" width=\"50\" \
+             height=\"50\" rx=\"0\" fill=\"rgb(255,255,255)\" fill-opacity=\"1\" />\n</g>\n"
+        ));
+        let second = "<clipPath id=\"clip2\"><rect x=\"0\" y=\"0\" width=\"20\" height=\"20\"/>";
+        assert!(svg.contains(second));
+    }
+
+    #[test]
+    fn escapes_svg_markup() {
+        assert_eq!(escape_svg("<a & b>"), "&lt;a &amp; b&gt;");
+        assert_eq!(escape_svg("&lt;"), "&amp;lt;");
+    }
+
+    #[test]
+    fn snapshots_ignore_rounding_and_caches() {
+        let bounds = Rectangle::new(Point::new(1.0, 2.0), Size::new(30.0, 40.0));
+        let nudged = Rectangle::new(Point::new(1.001, 2.0), Size::new(30.0, 39.999));
+        let red = Color::from_rgb(1.0, 0.0, 0.0);
+        let cached = Primitive::Cache {
+            content: std::sync::Arc::new(quad(nudged, red)),
+        };
+
+        let snapshot = PrimitiveSnapshot::new(&quad(bounds, red));
+        assert!(snapshot.is_some());
+        assert_eq!(snapshot, PrimitiveSnapshot::new(&cached));
+        assert_ne!(
+            snapshot,
+            PrimitiveSnapshot::new(&quad(bounds, Color::BLACK))
+        );
+        assert_eq!(PrimitiveSnapshot::new(&Primitive::None), None);
+    }
+}
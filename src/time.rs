@@ -0,0 +1,61 @@
+//! Time-based subscriptions, driven by Bevy's `Time`.
+//!
+//! Iced applications use subscriptions like `iced::time::every` to receive messages on a
+//! schedule. Create the equivalent [`Subscription`] with [`every`] and pass it to
+//! [`IcedContext::subscribe`](crate::IcedContext::subscribe) every frame:
+//! ```ignore
+//! fn ui_system(mut ctx: IcedContext<UiMessage>) {
+//!     ctx.subscribe(bevy_iced::time::every(Duration::from_secs(1)).map(UiMessage::Tick));
+//!     ctx.display(clock());
+//! }
+//! ```
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+pub use iced_native::time::Instant;
+
+/// A timer that produces a message of type `T` every time it fires.
+pub struct Subscription<T> {
+    pub(crate) period: Duration,
+    pub(crate) id: Option<u64>,
+    on_tick: Box<dyn Fn(Instant) -> T>,
+}
+
+impl<T: 'static> Subscription<T> {
+    /// Transforms the messages of the subscription.
+    pub fn map<U>(self, f: impl Fn(T) -> U + 'static) -> Subscription<U> {
+        let on_tick = self.on_tick;
+        Subscription {
+            period: self.period,
+            id: self.id,
+            on_tick: Box::new(move |instant| f(on_tick(instant))),
+        }
+    }
+
+    /// Identifies the subscription, to keep separate timers for subscriptions with the same
+    /// period whose messages are the same variant.
+    pub fn with_id(mut self, id: impl Hash) -> Self {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        self.id = Some(hasher.finish());
+        self
+    }
+
+    pub(crate) fn tick(&self, instant: Instant) -> T {
+        (self.on_tick)(instant)
+    }
+}
+
+/// Returns a [`Subscription`] that produces the current [`Instant`] every `duration`.
+///
+/// The time is measured by Bevy's `Time`, so the timer only fires once per frame and skips
+/// ticks that were missed during long frames.
+pub fn every(duration: Duration) -> Subscription<Instant> {
+    Subscription {
+        period: duration,
+        id: None,
+        on_tick: Box::new(|instant| instant),
+    }
+}
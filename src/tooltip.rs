@@ -0,0 +1,226 @@
+use std::time::Duration;
+
+use bevy_ecs::prelude::Entity;
+use bevy_ecs::system::Resource;
+use iced_native::event::{self, Event};
+use iced_native::layout::{self, Layout};
+use iced_native::widget::{Operation, Tree};
+use iced_native::{
+    mouse, overlay, renderer, Clipboard, Element, Length, Point, Rectangle, Shell, Size, Widget,
+};
+
+/// How the tooltips of [`IcedContext::tooltip_for`](crate::IcedContext::tooltip_for) are
+/// shown and placed.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct IcedTooltipSettings {
+    /// How long the tooltip of an entity is requested before it's shown.
+    pub show_delay: Duration,
+    /// How long a tooltip survives without being requested. Requests that resume within it
+    /// don't start the show delay over, and the tooltip of another entity requested within it
+    /// is shown right away.
+    pub hide_delay: Duration,
+    /// The camera that the entities are seen through, or `None` for the active camera with the
+    /// highest order that renders to a window.
+    pub camera: Option<Entity>,
+    /// The space between the projected entity and the tooltip.
+    pub gap: f32,
+    /// The space kept between the tooltip and the edges of the UI.
+    pub margin: f32,
+}
+
+impl Default for IcedTooltipSettings {
+    fn default() -> Self {
+        Self {
+            show_delay: Duration::from_millis(500),
+            hide_delay: Duration::from_millis(200),
+            camera: None,
+            gap: 8.0,
+            margin: 4.0,
+        }
+    }
+}
+
+// The tooltip requested for a message type, and when.
+#[derive(Default)]
+pub(crate) struct TooltipState {
+    entity: Option<Entity>,
+    // When the requests for the entity started, in the time of the app.
+    since: Duration,
+    last_requested: Duration,
+    shown: bool,
+}
+
+impl TooltipState {
+    // Records a request for the tooltip of `entity` at `now`, and returns how long until it's
+    // shown, or zero if it's shown now.
+    pub(crate) fn request(
+        &mut self,
+        entity: Entity,
+        now: Duration,
+        settings: &IcedTooltipSettings,
+    ) -> Duration {
+        let resumed = now.saturating_sub(self.last_requested) <= settings.hide_delay;
+        if self.entity != Some(entity) || !resumed {
+            // Moving from a shown tooltip to another one skips the show delay.
+            self.since = if self.shown && resumed {
+                now.saturating_sub(settings.show_delay)
+            } else {
+                now
+            };
+            self.entity = Some(entity);
+        }
+        self.last_requested = now;
+        let remaining = settings
+            .show_delay
+            .saturating_sub(now.saturating_sub(self.since));
+        self.shown = remaining.is_zero();
+        remaining
+    }
+}
+
+// An element centered above its anchor, or below it when there's no room above, and kept
+// within the margins of the UI.
+pub(crate) struct Tooltip<'a, M, Renderer> {
+    pub(crate) anchor: Point,
+    pub(crate) gap: f32,
+    pub(crate) margin: f32,
+    pub(crate) content: Element<'a, M, Renderer>,
+}
+
+impl<'a, M, Renderer: iced_native::Renderer> Widget<M, Renderer> for Tooltip<'a, M, Renderer> {
+    fn width(&self) -> Length {
+        Length::Fill
+    }
+
+    fn height(&self) -> Length {
+        Length::Fill
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn layout(&self, renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let bounds = limits.max();
+        let max = Size::new(
+            (bounds.width - 2.0 * self.margin).max(0.0),
+            (bounds.height - 2.0 * self.margin).max(0.0),
+        );
+        let mut content = self
+            .content
+            .as_widget()
+            .layout(renderer, &layout::Limits::new(Size::ZERO, max));
+        let size = content.size();
+
+        let x = self.anchor.x - size.width / 2.0;
+        let mut y = self.anchor.y - self.gap - size.height;
+        if y < self.margin {
+            y = self.anchor.y + self.gap;
+        }
+        let x = x
+            .min(bounds.width - self.margin - size.width)
+            .max(self.margin);
+        let y = y
+            .min(bounds.height - self.margin - size.height)
+            .max(self.margin);
+        content.move_to(Point::new(x, y));
+        layout::Node::with_children(bounds, vec![content])
+    }
+
+    fn operate(
+        &self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+        operation: &mut dyn Operation<M>,
+    ) {
+        let layout = layout.children().next().unwrap();
+        self.content
+            .as_widget()
+            .operate(&mut tree.children[0], layout, renderer, operation);
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        renderer: &Renderer,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, M>,
+    ) -> event::Status {
+        self.content.as_widget_mut().on_event(
+            &mut tree.children[0],
+            event,
+            layout.children().next().unwrap(),
+            cursor_position,
+            renderer,
+            clipboard,
+            shell,
+        )
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+        renderer: &Renderer,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout.children().next().unwrap(),
+            cursor_position,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Renderer::Theme,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor_position: Point,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout.children().next().unwrap(),
+            cursor_position,
+            viewport,
+        );
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'_>,
+        renderer: &Renderer,
+    ) -> Option<overlay::Element<'b, M, Renderer>> {
+        self.content.as_widget_mut().overlay(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+        )
+    }
+}
+
+impl<'a, M: 'a, Renderer: iced_native::Renderer + 'a> From<Tooltip<'a, M, Renderer>>
+    for Element<'a, M, Renderer>
+{
+    fn from(tooltip: Tooltip<'a, M, Renderer>) -> Self {
+        Element::new(tooltip)
+    }
+}
@@ -0,0 +1,102 @@
+use bevy_ecs::change_detection::{DetectChanges, Ref};
+use bevy_ecs::event::Event;
+use bevy_ecs::prelude::{Component, Entity, Query, RemovedComponents, With};
+use bevy_render::view::RenderLayers;
+use iced_native::{Element, Vector};
+
+use crate::{IcedCamera, IcedContext, Phase};
+
+/// A UI attached to an entity, displayed every frame for as long as the entity has it.
+///
+/// The interface state belongs to the entity, and is dropped when the component is removed
+/// or the entity is despawned.
+///
+/// The view is called every frame, unless [`IcedUi::reuse_frames`] is set. Register the message
+/// type with [`IcedAppExt::add_iced_ui`](crate::IcedAppExt::add_iced_ui):
+/// ```ignore
+/// app.add_iced_ui::<UiMessage>();
+///
+/// commands.spawn(IcedUi::new(|| text("Quest updated!").into()));
+/// ```
+#[derive(Component)]
+pub struct IcedUi<M: Event, Theme: Send + Sync + 'static = iced_wgpu::Theme> {
+    view: Box<dyn Fn() -> Element<'static, M, iced_wgpu::Renderer<Theme>> + Send + Sync>,
+    /// The window, [`IcedRenderTarget`](crate::IcedRenderTarget) or
+    /// [`IcedCamera`](crate::IcedCamera) to display the UI in, or `None` for the primary
+    /// window. Entities with `RenderLayers` are instead displayed in every `IcedCamera` that
+    /// sees one of their layers.
+    pub window: Option<Entity>,
+    /// Whether the view depends only on the component, so that the last frame is drawn again
+    /// without calling the view while the component is unchanged and the UI receives no input.
+    /// Mutate the component, or mark it changed with `set_changed`, to show other state.
+    ///
+    /// Views that read shared state, e.g. through an `Arc` or captured resources, show stale
+    /// data when this is set, so it's off by default.
+    pub reuse_frames: bool,
+}
+
+impl<M: Event, Theme: Send + Sync + 'static> IcedUi<M, Theme> {
+    /// Creates a UI that shows the element returned by `view` in the primary window.
+    pub fn new(
+        view: impl Fn() -> Element<'static, M, iced_wgpu::Renderer<Theme>> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            view: Box::new(view),
+            window: None,
+            reuse_frames: false,
+        }
+    }
+
+    /// Display the UI in the given window or render target instead of the primary window.
+    pub fn in_window(mut self, window: Entity) -> Self {
+        self.window = Some(window);
+        self
+    }
+
+    /// Draw the last frame again while the component is unchanged, see
+    /// [`IcedUi::reuse_frames`].
+    pub fn reusing_frames(mut self) -> Self {
+        self.reuse_frames = true;
+        self
+    }
+}
+
+/// Displays the [`IcedUi`] components with the message type `M`.
+pub fn display_ui_components<M: Event, Theme: Send + Sync + 'static>(
+    uis: Query<(Entity, Ref<IcedUi<M, Theme>>, Option<&RenderLayers>)>,
+    cameras: Query<(Entity, Option<&RenderLayers>), With<IcedCamera>>,
+    mut removed: RemovedComponents<IcedUi<M, Theme>>,
+    mut ctx: IcedContext<M, Theme>,
+) {
+    for entity in removed.iter() {
+        ctx.cache_map.remove_owner::<M>(entity);
+    }
+    let primary_window = ctx.primary_window.get_single().ok();
+    for (entity, ui, layers) in uis.iter() {
+        let targets: Vec<Entity> = match layers {
+            Some(layers) => cameras
+                .iter()
+                .filter(|(_, camera_layers)| {
+                    camera_layers
+                        .copied()
+                        .unwrap_or_default()
+                        .intersects(layers)
+                })
+                .map(|(camera, _)| camera)
+                .collect(),
+            None => ui.window.or(primary_window).into_iter().collect(),
+        };
+        for target in targets {
+            let translation = Vector::new(0.0, 0.0);
+            ctx.display_translated(
+                target,
+                || (ui.view)(),
+                translation,
+                None,
+                Some(entity),
+                Phase::Both,
+                ui.reuse_frames.then(|| ui.last_changed().into()),
+            );
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+use bevy::winit::WinitPlugin;
+use bevy_iced::iced::widget::{button, text};
+use bevy_iced::iced::Point;
+use bevy_iced::testing::IcedTestHarness;
+use bevy_iced::{IcedContext, IcedPlugin};
+
+#[derive(Clone, Debug, PartialEq)]
+enum UiMessage {
+    Pressed,
+}
+
+#[derive(Resource, Default)]
+struct Counter {
+    presses: usize,
+    views: usize,
+}
+
+fn ui_system(mut counter: ResMut<Counter>, mut ctx: IcedContext<UiMessage>) {
+    let Counter { presses, views } = &mut *counter;
+    let presses = *presses;
+    ctx.display_retained(0, || {
+        *views += 1;
+        button(text(format!("Pressed {presses}")))
+            .on_press(UiMessage::Pressed)
+            .into()
+    });
+}
+
+fn count_presses(mut messages: EventReader<UiMessage>, mut counter: ResMut<Counter>) {
+    counter.presses += messages.iter().count();
+}
+
+#[test]
+#[ignore = "needs a render device"]
+fn button_press_rebuilds_retained_view() {
+    let mut app = App::new();
+    app.add_plugins(
+        DefaultPlugins
+            .build()
+            .disable::<WinitPlugin>()
+            .set(WindowPlugin {
+                primary_window: None,
+                ..default()
+            }),
+    )
+    .add_plugin(IcedPlugin::default())
+    .add_event::<UiMessage>()
+    .init_resource::<Counter>()
+    .add_system(ui_system)
+    .add_system(count_presses.after(ui_system));
+
+    let mut harness = IcedTestHarness::new(app);
+    harness.update_frames(2);
+    assert!(
+        !harness.snapshot().is_empty(),
+        "the UIs aren't displayed without a render device"
+    );
+    let views = harness.app().world.resource::<Counter>().views;
+    harness.update();
+    assert_eq!(harness.app().world.resource::<Counter>().views, views);
+
+    harness.click(Point::new(5.0, 5.0));
+    harness.update();
+    harness.update();
+    let counter = harness.app().world.resource::<Counter>();
+    assert_eq!(counter.presses, 1);
+    assert_eq!(counter.views, views + 1);
+}